@@ -0,0 +1,255 @@
+//! Host-side simulator, runs the Controller against a terminal LED driver.
+//!
+//! ```bash
+//! ./run-simulator
+//! ```
+
+use night_light_lib::*;
+use std::cell::RefCell;
+use std::io::{self, Read, Write};
+use std::process::Command;
+use std::rc::Rc;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time;
+
+/// Matches the controller update timer in the firmware, 200 Hz
+const UPDATE_PERIOD_MS: u32 = 5;
+
+/// Real time between terminal redraws
+const RENDER_PERIOD: time::Duration = time::Duration::from_millis(20);
+
+const MAX_SPEED: u32 = 64;
+
+/// Wall clock time the simulation starts at
+const START_TIME: (u8, u8) = (6, 0);
+
+/// The wake key sets the wake time this far ahead, the sunrise starts right away
+const WAKE_AHEAD: Duration = Duration::TEN_MINUTES;
+
+/// Same layout as the 24 key remote
+const KEYMAP: &[(u8, Button)] = &[
+    (b'1', Button::BrightnessUp),
+    (b'2', Button::BrightnessDown),
+    (b'3', Button::Off),
+    (b'4', Button::On),
+    (b'q', Button::Red),
+    (b'w', Button::Green),
+    (b'e', Button::Blue),
+    (b'r', Button::White),
+    (b'a', Button::Red1),
+    (b's', Button::Green1),
+    (b'd', Button::Blue1),
+    (b'f', Button::Flash),
+    (b'z', Button::Red2),
+    (b'x', Button::Green2),
+    (b'c', Button::Blue2),
+    (b'v', Button::Strobe),
+    (b't', Button::Red3),
+    (b'y', Button::Green3),
+    (b'u', Button::Blue3),
+    (b'i', Button::Fade),
+    (b'g', Button::Red4),
+    (b'h', Button::Green4),
+    (b'j', Button::Blue4),
+    (b'k', Button::Smooth),
+];
+
+const KEY_AUTO_ON: u8 = b'o';
+/// Shift+4, holds On, a press then the hold gesture
+const KEY_HOLD_ON: u8 = b'$';
+const KEY_WAKE: u8 = b'p';
+const KEY_LONG_PRESS: u8 = b'l';
+const KEY_SPEED_UP: u8 = b'+';
+const KEY_SPEED_DOWN: u8 = b'-';
+const KEY_CTRL_C: u8 = 3;
+const KEY_ESC: u8 = 27;
+
+/// Terminal colors are already perceptual (sRGB), so unlike the Sk6812w driver
+/// there is no lightness correction stage
+struct TerminalLedDriver {
+    brightness: u8,
+    frame: Rc<RefCell<Frame>>,
+}
+
+impl InfallibleLedDriver for TerminalLedDriver {
+    const NUM_LEDS: usize = MAX_NUM_LEDS;
+
+    fn set_brightness(&mut self, brightness: u8) {
+        self.brightness = brightness;
+    }
+
+    fn write_pixels<I>(&mut self, pixels: I)
+    where
+        I: Iterator<Item = RGBW8>,
+    {
+        let mut frame = self.frame.borrow_mut();
+        *frame = FRAME_OFF;
+        for (p, color) in frame.iter_mut().zip(brightness_iter(
+            pixels.take(Self::NUM_LEDS),
+            self.brightness,
+        )) {
+            *p = color;
+        }
+    }
+}
+
+/// Approximate a RGBW pixel on an RGB terminal, white is the ring's warm white
+fn to_rgb(p: &RGBW8) -> (u8, u8, u8) {
+    let w = p.a.0 as u16;
+    let mix = |c: u8, white: u16| (c as u16 + white * w / 255).min(255) as u8;
+    (mix(p.r, 255), mix(p.g, 214), mix(p.b, 170))
+}
+
+fn render(
+    frame: &Frame,
+    now: Instant,
+    time: Option<TimeOfDay>,
+    speed: u32,
+    brightness: u8,
+) -> io::Result<()> {
+    let mut out = io::stdout().lock();
+    write!(out, "\r")?;
+    for p in frame.iter() {
+        let (r, g, b) = to_rgb(p);
+        write!(out, "\x1b[38;2;{};{};{}m\u{2588}\u{2588} ", r, g, b)?;
+    }
+    write!(
+        out,
+        "\x1b[0m t={}ms time={} speed=x{} brightness={}\x1b[K",
+        now,
+        time.map(|t| t.to_string()).unwrap_or_default(),
+        speed,
+        brightness
+    )?;
+    out.flush()
+}
+
+fn print_help() {
+    print!("Keys (remote layout):\r\n");
+    for row in KEYMAP.chunks(4) {
+        for (key, btn) in row {
+            print!("  {} {:<16}", *key as char, btn.to_string());
+        }
+        print!("\r\n");
+    }
+    print!(
+        "  {} AutoOn (vibration), {} hold On (sleep timer), {} wake in {} minutes, {} button long press, {}/{} simulation speed, Esc quit\r\n\r\n",
+        KEY_AUTO_ON as char,
+        KEY_HOLD_ON as char,
+        KEY_WAKE as char,
+        WAKE_AHEAD.as_millis() / 60_000,
+        KEY_LONG_PRESS as char,
+        KEY_SPEED_UP as char,
+        KEY_SPEED_DOWN as char
+    );
+}
+
+fn spawn_key_reader() -> Receiver<u8> {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let mut stdin = io::stdin();
+        let mut byte = [0; 1];
+        while let Ok(1) = stdin.read(&mut byte) {
+            if tx.send(byte[0]).is_err() {
+                break;
+            }
+        }
+    });
+    rx
+}
+
+fn set_raw_mode(raw: bool) {
+    let args: &[&str] = if raw {
+        &["raw", "-echo"]
+    } else {
+        &["-raw", "echo"]
+    };
+    Command::new("stty").args(args).status().ok();
+}
+
+fn main() -> io::Result<()> {
+    let frame = Rc::new(RefCell::new(FRAME_OFF));
+    let driver = TerminalLedDriver {
+        brightness: u8::MAX,
+        frame: frame.clone(),
+    };
+    // Simulated clock, advanced by the main loop instead of SysTick
+    let clock = MockClock::new();
+    let mut controller = Controller::new(driver, &clock, ControllerConfig::default())
+        .expect("Invalid controller config");
+    // Simulated wall clock, advanced along with the simulated clock
+    let mut rtc = MockRealTimeClock::new();
+    rtc.set_time_of_day(TimeOfDay::new(START_TIME.0, START_TIME.1, 0).unwrap())
+        .expect("Failed to set the simulated time");
+    let mut ms_since_second = 0;
+
+    set_raw_mode(true);
+    print_help();
+    let keys = spawn_key_reader();
+    let mut speed = 1;
+    let mut ms_since_update = 0;
+    let mut last_render = time::Instant::now();
+
+    'sim: loop {
+        while let Ok(key) = keys.try_recv() {
+            match key {
+                KEY_ESC | KEY_CTRL_C => break 'sim,
+                KEY_AUTO_ON => controller.handle_auto_on_event(),
+                KEY_HOLD_ON => {
+                    controller.handle_ir_gesture(IrGesture::Press(Button::On));
+                    controller.handle_ir_gesture(IrGesture::Hold(Button::On));
+                }
+                KEY_WAKE => controller.set_wake_time(rtc.time_of_day().map(|t| {
+                    TimeOfDay::from_seconds(
+                        t.seconds_since_midnight() + (WAKE_AHEAD.as_millis() / 1000),
+                    )
+                })),
+                KEY_LONG_PRESS => controller.handle_button_gesture(Gesture::LongPress),
+                KEY_SPEED_UP => speed = (speed * 2).min(MAX_SPEED),
+                KEY_SPEED_DOWN => speed = (speed / 2).max(1),
+                _ => {
+                    if let Some((_, button)) = KEYMAP.iter().find(|(k, _)| *k == key) {
+                        controller.handle_ir_command(IrCommand {
+                            button: *button,
+                            repeat: false,
+                        });
+                    }
+                }
+            }
+        }
+
+        // Advance the simulated clock by one real render period, scaled by the speed
+        let elapsed_ms = last_render.elapsed().as_millis() as u32 * speed;
+        last_render = time::Instant::now();
+        for _ in 0..elapsed_ms {
+            clock.advance(Duration::from_millis(1));
+            ms_since_update += 1;
+            if ms_since_update == UPDATE_PERIOD_MS {
+                ms_since_update = 0;
+                controller.update();
+            }
+            ms_since_second += 1;
+            if ms_since_second == 1000 {
+                ms_since_second = 0;
+                rtc.advance(Duration::ONE_SECOND);
+                if let Some(time) = rtc.time_of_day() {
+                    controller.handle_time_of_day(time);
+                }
+            }
+        }
+
+        render(
+            &frame.borrow(),
+            clock.now(),
+            rtc.time_of_day(),
+            speed,
+            controller.brightness(),
+        )?;
+        thread::sleep(RENDER_PERIOD);
+    }
+
+    set_raw_mode(false);
+    println!();
+    Ok(())
+}
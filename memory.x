@@ -1,6 +1,7 @@
 MEMORY
 {
   /* NOTE K = KiBi = 1024 bytes */
-  FLASH : ORIGIN = 0x08000000, LENGTH = 256K
+  /* The last 2 pages (4K) are reserved for the settings store */
+  FLASH : ORIGIN = 0x08000000, LENGTH = 252K
   RAM : ORIGIN = 0x20000000, LENGTH = 40K
 }
@@ -0,0 +1,236 @@
+use crate::{
+    AutoOnAction, Duration, Interpolation, IrAddressFilter, IrKeymap, Schedule, TimeOfDay,
+};
+
+/// Shortest AutoOn/ManualOn duration, anything less is over before the fade in finishes
+pub const MIN_ON_DURATION: Duration = Duration::ONE_SECOND;
+pub const MAX_ON_DURATION: Duration = Duration::TEN_HOURS;
+
+/// Fade and flash hold durations must be non-zero and at most this
+pub const MAX_FADE_DURATION: Duration = Duration::ONE_MINUTE;
+
+/// Strobe frequency range, higher is past what the millisecond clock can time evenly
+pub const MIN_STROBE_FREQUENCY_HZ: u8 = 1;
+pub const MAX_STROBE_FREQUENCY_HZ: u8 = 25;
+
+/// Shortest strobe on or off time, one controller update at 200 Hz,
+/// anything less can fall between two updates and never show
+pub const MIN_STROBE_PHASE: Duration = Duration::from_millis(5);
+
+/// How long Mode::Sleep takes to dim down before turning off
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub enum SleepTimer {
+    FifteenMinutes,
+    ThirtyMinutes,
+    SixtyMinutes,
+}
+
+impl SleepTimer {
+    pub fn from_u8(value: u8) -> Option<Self> {
+        use SleepTimer::*;
+        Some(match value {
+            0 => FifteenMinutes,
+            1 => ThirtyMinutes,
+            2 => SixtyMinutes,
+            _ => return None,
+        })
+    }
+
+    pub fn duration(self) -> Duration {
+        match self {
+            SleepTimer::FifteenMinutes => Duration::from_millis(15 * 60 * 1000),
+            SleepTimer::ThirtyMinutes => Duration::from_millis(30 * 60 * 1000),
+            SleepTimer::SixtyMinutes => Duration::ONE_HOUR,
+        }
+    }
+
+    /// Cycles 15 -> 30 -> 60 -> 15 minutes
+    pub fn next(self) -> Self {
+        match self {
+            SleepTimer::FifteenMinutes => SleepTimer::ThirtyMinutes,
+            SleepTimer::ThirtyMinutes => SleepTimer::SixtyMinutes,
+            SleepTimer::SixtyMinutes => SleepTimer::FifteenMinutes,
+        }
+    }
+}
+
+impl From<SleepTimer> for u8 {
+    fn from(t: SleepTimer) -> Self {
+        t as u8
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub enum ConfigError {
+    InvalidAutoOnDuration,
+    InvalidManualOnDuration,
+    InvalidFadeDuration,
+    InvalidStrobe,
+    InvalidScheduleWindow,
+}
+
+/// Controller timing, see `ControllerConfig::validate` for the allowed ranges
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct ControllerConfig {
+    /// How long the light stays on after an AutoOn event
+    pub auto_on_duration: Duration,
+    /// How long the light stays on after being turned on from the remote or button
+    pub manual_on_duration: Duration,
+    /// Duration of the fade when turning on/off or changing color
+    pub onoff_fade_duration: Duration,
+    /// How long Flash holds each color
+    pub flash_mode_hold_duration: Duration,
+    /// Strobe on/off cycles per second
+    pub strobe_mode_frequency_hz: u8,
+    /// Percentage of each strobe cycle spent on, 1..=99
+    pub strobe_mode_duty_percent: u8,
+    /// Duration of each color transition in the Fade and Smooth modes
+    pub fade_mode_fade_duration: Duration,
+    pub smooth_mode_fade_duration: Duration,
+    /// Color space used by the Fade and Smooth mode transitions
+    pub fade_mode_interpolation: Interpolation,
+    pub smooth_mode_interpolation: Interpolation,
+    pub sleep_timer: SleepTimer,
+    /// Sunrise alarm, the ramp reaches bright white at this time, minutes resolution
+    pub wake_time: Option<TimeOfDay>,
+    /// When AutoOn lights the room, empty lets it fire at any time
+    pub schedule: Schedule,
+    /// Remote keys from the IR learning mode, empty is the stock remote
+    pub ir_keymap: IrKeymap,
+    /// Remotes listened to, the stock remote unless changed.
+    /// The remote learned is added, if there's room
+    pub ir_addresses: IrAddressFilter,
+}
+
+impl Default for ControllerConfig {
+    fn default() -> Self {
+        ControllerConfig {
+            auto_on_duration: Duration::ONE_MINUTE,
+            manual_on_duration: Duration::ONE_MINUTE,
+            onoff_fade_duration: Duration::from_millis(2 * 1000),
+            flash_mode_hold_duration: Duration::ONE_SECOND,
+            strobe_mode_frequency_hz: 5,
+            strobe_mode_duty_percent: 50,
+            fade_mode_fade_duration: Duration::from_millis(20 * 1000),
+            smooth_mode_fade_duration: Duration::from_millis(10 * 1000),
+            fade_mode_interpolation: Interpolation::Hsv,
+            smooth_mode_interpolation: Interpolation::Hsv,
+            sleep_timer: SleepTimer::ThirtyMinutes,
+            wake_time: None,
+            schedule: Schedule::new(),
+            ir_keymap: IrKeymap::new(),
+            ir_addresses: IrAddressFilter::new(),
+        }
+    }
+}
+
+impl ControllerConfig {
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let on_duration_valid = |d: Duration| d >= MIN_ON_DURATION && d <= MAX_ON_DURATION;
+        let fade_duration_valid = |d: Duration| d > Duration::ZERO && d <= MAX_FADE_DURATION;
+
+        if !on_duration_valid(self.auto_on_duration) {
+            Err(ConfigError::InvalidAutoOnDuration)
+        } else if !on_duration_valid(self.manual_on_duration) {
+            Err(ConfigError::InvalidManualOnDuration)
+        } else if ![
+            self.onoff_fade_duration,
+            self.flash_mode_hold_duration,
+            self.fade_mode_fade_duration,
+            self.smooth_mode_fade_duration,
+        ]
+        .iter()
+        .all(|d| fade_duration_valid(*d))
+        {
+            Err(ConfigError::InvalidFadeDuration)
+        } else if !(MIN_STROBE_FREQUENCY_HZ..=MAX_STROBE_FREQUENCY_HZ)
+            .contains(&self.strobe_mode_frequency_hz)
+            || !(1..=99).contains(&self.strobe_mode_duty_percent)
+            || !self.strobe_phases_valid()
+        {
+            Err(ConfigError::InvalidStrobe)
+        } else if !self.schedule.windows().all(|w| match w.action {
+            AutoOnAction::Light(light) => on_duration_valid(light.duration),
+            _ => true,
+        }) {
+            Err(ConfigError::InvalidScheduleWindow)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Strobe cycle period and the on time within it
+    pub fn strobe_timing(&self) -> (Duration, Duration) {
+        let period = 1000 / self.strobe_mode_frequency_hz.max(1) as u32;
+        let on_time = period * self.strobe_mode_duty_percent as u32 / 100;
+        (
+            Duration::from_millis(period),
+            Duration::from_millis(on_time),
+        )
+    }
+
+    fn strobe_phases_valid(&self) -> bool {
+        let (period, on_time) = self.strobe_timing();
+        let off_time =
+            Duration::from_millis(period.as_millis().saturating_sub(on_time.as_millis()));
+        on_time >= MIN_STROBE_PHASE && off_time >= MIN_STROBE_PHASE
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_is_valid() {
+        assert_eq!(ControllerConfig::default().validate(), Ok(()));
+    }
+
+    #[test]
+    fn out_of_range_durations_are_rejected() {
+        let config = ControllerConfig {
+            auto_on_duration: Duration::from_millis(999),
+            ..Default::default()
+        };
+        assert_eq!(config.validate(), Err(ConfigError::InvalidAutoOnDuration));
+
+        let config = ControllerConfig {
+            manual_on_duration: Duration::from_millis(MAX_ON_DURATION.as_millis() + 1),
+            ..Default::default()
+        };
+        assert_eq!(config.validate(), Err(ConfigError::InvalidManualOnDuration));
+
+        let config = ControllerConfig {
+            fade_mode_fade_duration: Duration::ZERO,
+            ..Default::default()
+        };
+        assert_eq!(config.validate(), Err(ConfigError::InvalidFadeDuration));
+    }
+
+    fn strobe(frequency_hz: u8, duty_percent: u8) -> Result<(), ConfigError> {
+        ControllerConfig {
+            strobe_mode_frequency_hz: frequency_hz,
+            strobe_mode_duty_percent: duty_percent,
+            ..Default::default()
+        }
+        .validate()
+    }
+
+    #[test]
+    fn strobe_phases_shorter_than_an_update_are_rejected() {
+        assert_eq!(strobe(1, 1), Ok(()));
+        assert_eq!(strobe(1, 99), Ok(()));
+        // 40 ms period, 5 ms on
+        assert_eq!(strobe(25, 13), Ok(()));
+        assert_eq!(strobe(25, 12), Err(ConfigError::InvalidStrobe));
+        assert_eq!(strobe(25, 1), Err(ConfigError::InvalidStrobe));
+        // 40 ms period, 5 ms off
+        assert_eq!(strobe(25, 89), Ok(()));
+        assert_eq!(strobe(25, 90), Err(ConfigError::InvalidStrobe));
+        assert_eq!(strobe(25, 99), Err(ConfigError::InvalidStrobe));
+        assert_eq!(strobe(26, 50), Err(ConfigError::InvalidStrobe));
+        assert_eq!(strobe(0, 50), Err(ConfigError::InvalidStrobe));
+        assert_eq!(strobe(5, 0), Err(ConfigError::InvalidStrobe));
+        assert_eq!(strobe(5, 100), Err(ConfigError::InvalidStrobe));
+    }
+}
@@ -0,0 +1,702 @@
+use crate::{
+    AutoOnAction, AutoOnLight, Duration, EffectId, IrAddress, IrProtocol, Mode, ScheduleWindow,
+    TimeOfDay, White, MAX_SCHEDULE_WINDOWS, RGBW8,
+};
+use core::str;
+use heapless::{consts::U64, spsc, Vec};
+use log::LevelFilter;
+
+/// Most arguments a command takes
+const MAX_ARGS: usize = 4;
+
+/// Command names and arguments, shown by `help`
+pub const CONSOLE_USAGE: &[&str] = &[
+    "on",
+    "off",
+    "color RRGGBB[WW]",
+    "mode sleep|learn|<effect>",
+    "time HH:MM[:SS]",
+    "wake HH:MM|off",
+    "schedule 1-4 HH:MM-HH:MM off|default|RRGGBB[WW] <seconds>",
+    "schedule 1-4 clear",
+    "ir allow|deny nec|nec16|samsung|rc5|rc6 <address>",
+    "ir allow|deny any",
+    "status",
+    "log off|error|warn|info|debug|trace",
+    "help",
+];
+
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub enum ConsoleError {
+    UnknownCommand,
+    MissingArgument,
+    TooManyArguments,
+    InvalidArgument,
+    /// Not printable ASCII
+    InvalidCharacter,
+    LineTooLong,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum ConsoleCommand {
+    /// Resumes the last mode and color selected
+    On,
+    Off,
+    Color(RGBW8),
+    /// Only Mode::Sleep, Mode::IrLearning and the effects are selectable
+    Mode(Mode),
+    /// Sets the RTC
+    Time(TimeOfDay),
+    /// None turns the sunrise alarm off
+    WakeTime(Option<TimeOfDay>),
+    /// Window index from 0, None clears it
+    Schedule(usize, Option<ScheduleWindow>),
+    /// Adds the remote to the `IrAddressFilter`
+    IrAllow(IrAddress),
+    IrDeny(IrAddress),
+    /// Opts in or out of accepting every remote
+    IrAny(bool),
+    Status,
+    LogLevel(LevelFilter),
+    Help,
+}
+
+impl ConsoleCommand {
+    /// Words are separated by whitespace, names are case insensitive
+    pub fn parse(line: &str) -> Result<Self, ConsoleError> {
+        let mut words = line.split_whitespace();
+        let name = words.next().ok_or(ConsoleError::UnknownCommand)?;
+        let mut args = [""; MAX_ARGS];
+        let mut count = 0;
+        for word in words {
+            *args.get_mut(count).ok_or(ConsoleError::TooManyArguments)? = word;
+            count += 1;
+        }
+        let is = |n: &&str| name.eq_ignore_ascii_case(n);
+        Ok(match args[..count] {
+            [] if is(&"on") => ConsoleCommand::On,
+            [] if is(&"off") => ConsoleCommand::Off,
+            [] if is(&"status") => ConsoleCommand::Status,
+            [] if is(&"help") => ConsoleCommand::Help,
+            [arg] if is(&"color") => ConsoleCommand::Color(parse_color(arg)?),
+            [arg] if is(&"mode") => ConsoleCommand::Mode(parse_mode(arg)?),
+            [arg] if is(&"time") => ConsoleCommand::Time(parse_time(arg)?),
+            [arg] if is(&"wake") => ConsoleCommand::WakeTime(if arg.eq_ignore_ascii_case("off") {
+                None
+            } else {
+                Some(parse_time(arg)?)
+            }),
+            [arg] if is(&"log") => {
+                ConsoleCommand::LogLevel(arg.parse().map_err(|_| ConsoleError::InvalidArgument)?)
+            }
+            [index, ref window @ ..] if is(&"schedule") && !window.is_empty() => {
+                ConsoleCommand::Schedule(parse_window_index(index)?, parse_window(window)?)
+            }
+            [action, any] if is(&"ir") && any.eq_ignore_ascii_case("any") => {
+                ConsoleCommand::IrAny(parse_ir_action(action)?)
+            }
+            [action, protocol, address] if is(&"ir") => {
+                let address = parse_ir_address(protocol, address)?;
+                if parse_ir_action(action)? {
+                    ConsoleCommand::IrAllow(address)
+                } else {
+                    ConsoleCommand::IrDeny(address)
+                }
+            }
+            [_, ..] if ["on", "off", "status", "help"].iter().any(is) => {
+                return Err(ConsoleError::TooManyArguments)
+            }
+            [_, _, ..] if ["color", "mode", "time", "wake", "log"].iter().any(is) => {
+                return Err(ConsoleError::TooManyArguments)
+            }
+            [_, _, _, _, ..] if is(&"ir") => return Err(ConsoleError::TooManyArguments),
+            _ if ["color", "mode", "time", "wake", "log", "schedule", "ir"]
+                .iter()
+                .any(is) =>
+            {
+                return Err(ConsoleError::MissingArgument)
+            }
+            _ => return Err(ConsoleError::UnknownCommand),
+        })
+    }
+}
+
+/// Assembles the bytes received into lines, ended by CR or LF
+#[derive(Clone, Debug)]
+pub struct Console {
+    line: Vec<u8, U64>,
+    error: Option<ConsoleError>,
+}
+
+impl Console {
+    pub fn new() -> Self {
+        Console {
+            line: Vec::new(),
+            error: None,
+        }
+    }
+
+    /// Returns the command once its line ends, blank lines are ignored.
+    /// Backspace and delete drop the last character
+    pub fn push(&mut self, byte: u8) -> Option<Result<ConsoleCommand, ConsoleError>> {
+        match byte {
+            b'\r' | b'\n' => {
+                let result = match self.error.take() {
+                    Some(e) => Some(Err(e)),
+                    None => {
+                        // Unwrap ok, only printable ASCII is kept
+                        let line = str::from_utf8(&self.line).unwrap();
+                        if line.trim().is_empty() {
+                            None
+                        } else {
+                            Some(ConsoleCommand::parse(line))
+                        }
+                    }
+                };
+                // Not `clear`, heapless 0.6.0 indexes past the end when truncating
+                self.line = Vec::new();
+                result
+            }
+            0x08 | 0x7F => {
+                self.line.pop();
+                None
+            }
+            b' '..=b'~' | b'\t' => {
+                if self.line.push(byte).is_err() {
+                    self.error.get_or_insert(ConsoleError::LineTooLong);
+                }
+                None
+            }
+            _ => {
+                self.error.get_or_insert(ConsoleError::InvalidCharacter);
+                None
+            }
+        }
+    }
+}
+
+impl Default for Console {
+    fn default() -> Self {
+        Console::new()
+    }
+}
+
+/// Bytes received, filled by the USART interrupt
+pub struct ConsoleQueue(spsc::Queue<u8, U64, u8, spsc::SingleCore>);
+
+impl ConsoleQueue {
+    pub const fn new() -> Self {
+        ConsoleQueue(spsc::Queue(unsafe { heapless::i::Queue::u8_sc() }))
+    }
+
+    pub fn dequeue(&mut self) -> Option<u8> {
+        self.0.dequeue()
+    }
+
+    pub fn enqueue(&mut self, item: u8) -> Result<(), u8> {
+        self.0.enqueue(item)
+    }
+}
+
+impl Default for ConsoleQueue {
+    fn default() -> Self {
+        ConsoleQueue::new()
+    }
+}
+
+/// RRGGBB or RRGGBBWW, an optional leading '#'
+fn parse_color(arg: &str) -> Result<RGBW8, ConsoleError> {
+    let hex = arg.strip_prefix('#').unwrap_or(arg);
+    if !matches!(hex.len(), 6 | 8) || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(ConsoleError::InvalidArgument);
+    }
+    let value = u32::from_str_radix(hex, 16).map_err(|_| ConsoleError::InvalidArgument)?;
+    let [r, g, b, w] = if hex.len() == 6 {
+        (value << 8).to_be_bytes()
+    } else {
+        value.to_be_bytes()
+    };
+    Ok(RGBW8::new_alpha(r, g, b, White(w)))
+}
+
+fn parse_mode(arg: &str) -> Result<Mode, ConsoleError> {
+    if arg.eq_ignore_ascii_case("sleep") {
+        Ok(Mode::Sleep)
+    } else if arg.eq_ignore_ascii_case("learn") {
+        Ok(Mode::IrLearning)
+    } else {
+        EffectId::enumerate()
+            .iter()
+            .find(|id| id.name().eq_ignore_ascii_case(arg))
+            .map(|id| Mode::Effect(*id))
+            .ok_or(ConsoleError::InvalidArgument)
+    }
+}
+
+/// True for allow, false for deny
+fn parse_ir_action(arg: &str) -> Result<bool, ConsoleError> {
+    if arg.eq_ignore_ascii_case("allow") {
+        Ok(true)
+    } else if arg.eq_ignore_ascii_case("deny") {
+        Ok(false)
+    } else {
+        Err(ConsoleError::InvalidArgument)
+    }
+}
+
+/// Protocol name and the address, decimal or 0x hex, as logged for rejected keys
+fn parse_ir_address(protocol: &str, address: &str) -> Result<IrAddress, ConsoleError> {
+    use IrProtocol::*;
+    let protocol = [
+        ("nec", Nec),
+        ("nec16", Nec16),
+        ("samsung", Samsung),
+        ("rc5", Rc5),
+        ("rc6", Rc6),
+    ]
+    .iter()
+    .find(|(name, _)| name.eq_ignore_ascii_case(protocol))
+    .map(|(_, p)| *p)
+    .ok_or(ConsoleError::InvalidArgument)?;
+    let (digits, radix) = match address.get(..2) {
+        Some(prefix) if prefix.eq_ignore_ascii_case("0x") => (&address[2..], 16),
+        _ => (address, 10),
+    };
+    match u16::from_str_radix(digits, radix) {
+        Ok(address) if digits.bytes().all(|b| b.is_ascii_hexdigit()) => {
+            Ok(IrAddress { protocol, address })
+        }
+        _ => Err(ConsoleError::InvalidArgument),
+    }
+}
+
+/// 1 up to `MAX_SCHEDULE_WINDOWS`, returned from 0
+fn parse_window_index(arg: &str) -> Result<usize, ConsoleError> {
+    match arg.parse::<usize>() {
+        Ok(i)
+            if arg.bytes().all(|b| b.is_ascii_digit())
+                && (1..=MAX_SCHEDULE_WINDOWS).contains(&i) =>
+        {
+            Ok(i - 1)
+        }
+        _ => Err(ConsoleError::InvalidArgument),
+    }
+}
+
+/// `clear`, or HH:MM-HH:MM followed by off, default, or a color and on duration in seconds
+fn parse_window(args: &[&str]) -> Result<Option<ScheduleWindow>, ConsoleError> {
+    let (span, action) = match args {
+        [clear] if clear.eq_ignore_ascii_case("clear") => return Ok(None),
+        [span, action @ ..] if !action.is_empty() => (span, action),
+        [_] => return Err(ConsoleError::MissingArgument),
+        _ => return Err(ConsoleError::InvalidArgument),
+    };
+    let mut times = span.splitn(2, '-');
+    let start = parse_time(times.next().unwrap_or(""))?;
+    let end = parse_time(times.next().ok_or(ConsoleError::InvalidArgument)?)?;
+    let action = match action {
+        [off] if off.eq_ignore_ascii_case("off") => AutoOnAction::Suppress,
+        [default] if default.eq_ignore_ascii_case("default") => AutoOnAction::Default,
+        // A color still needs its duration, anything else is not an action
+        [color] => {
+            parse_color(color)?;
+            return Err(ConsoleError::MissingArgument);
+        }
+        [color, seconds] => {
+            let seconds = match seconds.parse::<u32>() {
+                Ok(s) if seconds.bytes().all(|b| b.is_ascii_digit()) => s,
+                _ => return Err(ConsoleError::InvalidArgument),
+            };
+            AutoOnAction::Light(AutoOnLight {
+                color: parse_color(color)?,
+                duration: Duration::from_millis(
+                    seconds
+                        .checked_mul(1000)
+                        .ok_or(ConsoleError::InvalidArgument)?,
+                ),
+            })
+        }
+        _ => return Err(ConsoleError::InvalidArgument),
+    };
+    Ok(Some(ScheduleWindow { start, end, action }))
+}
+
+/// HH:MM or HH:MM:SS, 24 hour
+fn parse_time(arg: &str) -> Result<TimeOfDay, ConsoleError> {
+    let mut fields = [0_u8; 3];
+    let mut count = 0;
+    for field in arg.split(':') {
+        if count == fields.len()
+            || !matches!(field.len(), 1 | 2)
+            || !field.bytes().all(|b| b.is_ascii_digit())
+        {
+            return Err(ConsoleError::InvalidArgument);
+        }
+        // Unwrap ok, one or two digits
+        fields[count] = field.parse().unwrap();
+        count += 1;
+    }
+    if count < 2 {
+        return Err(ConsoleError::InvalidArgument);
+    }
+    TimeOfDay::new(fields[0], fields[1], fields[2]).ok_or(ConsoleError::InvalidArgument)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time(hours: u8, minutes: u8) -> TimeOfDay {
+        TimeOfDay::new(hours, minutes, 0).unwrap()
+    }
+
+    /// Feeds the bytes, only the last one may end a command
+    fn feed(console: &mut Console, bytes: &[u8]) -> Option<Result<ConsoleCommand, ConsoleError>> {
+        let (last, bytes) = bytes.split_last().unwrap();
+        for byte in bytes {
+            assert_eq!(console.push(*byte), None, "{:?}", *byte as char);
+        }
+        console.push(*last)
+    }
+
+    #[test]
+    fn commands_in_either_case() {
+        use ConsoleCommand::*;
+        for (lower, upper, cmd) in [
+            ("on", "ON", On),
+            ("off", "OFF", Off),
+            ("status", "STATUS", Status),
+            ("help", "HELP", Help),
+            (
+                "color ff8000",
+                "COLOR FF8000",
+                Color(RGBW8::new_alpha(255, 128, 0, White(0))),
+            ),
+            ("mode sleep", "MODE SLEEP", Mode(crate::Mode::Sleep)),
+            ("mode learn", "MODE LEARN", Mode(crate::Mode::IrLearning)),
+            (
+                "mode candle",
+                "MODE CANDLE",
+                Mode(crate::Mode::Effect(EffectId::Candle)),
+            ),
+            ("time 7:05", "TIME 7:05", Time(time(7, 5))),
+            ("wake 6:30", "WAKE 6:30", WakeTime(Some(time(6, 30)))),
+            ("wake off", "WAKE OFF", WakeTime(None)),
+            ("log debug", "LOG DEBUG", LogLevel(LevelFilter::Debug)),
+        ] {
+            assert_eq!(ConsoleCommand::parse(lower), Ok(cmd), "{}", lower);
+            assert_eq!(ConsoleCommand::parse(upper), Ok(cmd), "{}", upper);
+        }
+        assert_eq!(
+            ConsoleCommand::parse("  time\t7:05  "),
+            ConsoleCommand::parse("time 7:05")
+        );
+    }
+
+    #[test]
+    fn every_effect_is_a_mode() {
+        for id in EffectId::enumerate() {
+            let mut line = [0_u8; 32];
+            let name = id.name().as_bytes();
+            line[..5].copy_from_slice(b"mode ");
+            line[5..5 + name.len()].copy_from_slice(name);
+            let line = str::from_utf8(&line[..5 + name.len()]).unwrap();
+            assert_eq!(
+                ConsoleCommand::parse(line),
+                Ok(ConsoleCommand::Mode(Mode::Effect(*id)))
+            );
+        }
+    }
+
+    #[test]
+    fn colors() {
+        let color = |line| match ConsoleCommand::parse(line) {
+            Ok(ConsoleCommand::Color(c)) => Ok(c),
+            Ok(cmd) => panic!("{:?}", cmd),
+            Err(e) => Err(e),
+        };
+        assert_eq!(
+            color("color 102030"),
+            Ok(RGBW8::new_alpha(0x10, 0x20, 0x30, White(0)))
+        );
+        assert_eq!(
+            color("color #a0B0c0"),
+            Ok(RGBW8::new_alpha(0xa0, 0xb0, 0xc0, White(0)))
+        );
+        assert_eq!(
+            color("color 10203040"),
+            Ok(RGBW8::new_alpha(0x10, 0x20, 0x30, White(0x40)))
+        );
+        for bad in [
+            "color 12345g",
+            "color 12345",
+            "color 123456789",
+            "color ##123456",
+            "color 0x123456",
+            "color red",
+        ] {
+            assert_eq!(color(bad), Err(ConsoleError::InvalidArgument), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn times() {
+        let t = |line| ConsoleCommand::parse(line);
+        assert_eq!(t("time 0:0"), Ok(ConsoleCommand::Time(TimeOfDay::MIDNIGHT)));
+        assert_eq!(
+            t("time 23:59:59"),
+            Ok(ConsoleCommand::Time(TimeOfDay::new(23, 59, 59).unwrap()))
+        );
+        for bad in [
+            "time 24:00",
+            "time 12:60",
+            "time 12:00:60",
+            "time 1:2:3:4",
+            "time 12",
+            "time 12:",
+            "time :30",
+            "time 012:00",
+            "time 12:+5",
+            "wake 24:00",
+            "wake never",
+        ] {
+            assert_eq!(t(bad), Err(ConsoleError::InvalidArgument), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn argument_errors() {
+        use ConsoleError::*;
+        for (line, error) in [
+            ("", UnknownCommand),
+            ("bogus", UnknownCommand),
+            ("timer 7:05", UnknownCommand),
+            ("onn", UnknownCommand),
+            ("on now", TooManyArguments),
+            ("help me", TooManyArguments),
+            ("color", MissingArgument),
+            ("color 123456 7", TooManyArguments),
+            ("mode", MissingArgument),
+            ("mode disco", InvalidArgument),
+            ("mode autoon", InvalidArgument),
+            ("log", MissingArgument),
+            ("log loud", InvalidArgument),
+            ("time", MissingArgument),
+            ("time 7:05 now", TooManyArguments),
+            ("wake", MissingArgument),
+        ] {
+            assert_eq!(ConsoleCommand::parse(line), Err(error), "{:?}", line);
+        }
+    }
+
+    #[test]
+    fn schedule_window() {
+        assert_eq!(
+            ConsoleCommand::parse("schedule 1 20:00-07:00 off"),
+            Ok(ConsoleCommand::Schedule(
+                0,
+                Some(ScheduleWindow {
+                    start: time(20, 0),
+                    end: time(7, 0),
+                    action: AutoOnAction::Suppress
+                })
+            ))
+        );
+        assert_eq!(
+            ConsoleCommand::parse("SCHEDULE 4 6:00-7:30 Default"),
+            Ok(ConsoleCommand::Schedule(
+                3,
+                Some(ScheduleWindow {
+                    start: time(6, 0),
+                    end: time(7, 30),
+                    action: AutoOnAction::Default
+                })
+            ))
+        );
+        assert_eq!(
+            ConsoleCommand::parse("schedule 2 22:00-06:00 #200000 90"),
+            Ok(ConsoleCommand::Schedule(
+                1,
+                Some(ScheduleWindow {
+                    start: time(22, 0),
+                    end: time(6, 0),
+                    action: AutoOnAction::Light(AutoOnLight {
+                        color: RGBW8::new_alpha(0x20, 0, 0, White(0)),
+                        duration: Duration::from_millis(90 * 1000),
+                    })
+                })
+            ))
+        );
+        assert_eq!(
+            ConsoleCommand::parse("schedule 1 20:00-07:00 a0B0c0d0 1"),
+            Ok(ConsoleCommand::Schedule(
+                0,
+                Some(ScheduleWindow {
+                    start: time(20, 0),
+                    end: time(7, 0),
+                    action: AutoOnAction::Light(AutoOnLight {
+                        color: RGBW8::new_alpha(0xa0, 0xb0, 0xc0, White(0xd0)),
+                        duration: Duration::from_millis(1000),
+                    })
+                })
+            ))
+        );
+        assert_eq!(
+            ConsoleCommand::parse("schedule 3 clear"),
+            Ok(ConsoleCommand::Schedule(2, None))
+        );
+    }
+
+    #[test]
+    fn ir_allow_and_deny() {
+        let address = |protocol, address| IrAddress { protocol, address };
+        assert_eq!(
+            ConsoleCommand::parse("ir allow nec 0"),
+            Ok(ConsoleCommand::IrAllow(address(IrProtocol::Nec, 0)))
+        );
+        assert_eq!(
+            ConsoleCommand::parse("IR Deny RC5 20"),
+            Ok(ConsoleCommand::IrDeny(address(IrProtocol::Rc5, 20)))
+        );
+        assert_eq!(
+            ConsoleCommand::parse("ir allow nec16 0xBF40"),
+            Ok(ConsoleCommand::IrAllow(address(IrProtocol::Nec16, 0xbf40)))
+        );
+        assert_eq!(
+            ConsoleCommand::parse("ir allow samsung 0X7"),
+            Ok(ConsoleCommand::IrAllow(address(IrProtocol::Samsung, 7)))
+        );
+        assert_eq!(
+            ConsoleCommand::parse("ir allow ANY"),
+            Ok(ConsoleCommand::IrAny(true))
+        );
+        assert_eq!(
+            ConsoleCommand::parse("ir deny any"),
+            Ok(ConsoleCommand::IrAny(false))
+        );
+
+        use ConsoleError::*;
+        for (line, error) in [
+            ("ir", MissingArgument),
+            ("ir allow", MissingArgument),
+            ("ir allow nec", MissingArgument),
+            ("ir block any", InvalidArgument),
+            ("ir allow nec 0 1", TooManyArguments),
+            ("ir block nec 0", InvalidArgument),
+            ("ir allow sony 0", InvalidArgument),
+            ("ir allow nec 65536", InvalidArgument),
+            ("ir allow nec -1", InvalidArgument),
+            ("ir allow nec 0x", InvalidArgument),
+            ("ir allow nec 0x+1", InvalidArgument),
+            ("ir allow nec 1f", InvalidArgument),
+        ] {
+            assert_eq!(ConsoleCommand::parse(line), Err(error), "{}", line);
+        }
+    }
+
+    #[test]
+    fn invalid_schedule_window() {
+        use ConsoleError::*;
+        for (line, error) in [
+            ("schedule", MissingArgument),
+            ("schedule 1", MissingArgument),
+            ("schedule 1 20:00-07:00", MissingArgument),
+            ("schedule 1 20:00-07:00 200000", MissingArgument),
+            ("schedule 0 20:00-07:00 off", InvalidArgument),
+            ("schedule 5 clear", InvalidArgument),
+            ("schedule 1 20:00 off", InvalidArgument),
+            ("schedule 1 20:00-25:00 off", InvalidArgument),
+            ("schedule 1 20:00-07:00 sometimes", InvalidArgument),
+            ("schedule 1 20:00-07:00 sometimes 90", InvalidArgument),
+            ("schedule 1 20:00-07:00 12345g 90", InvalidArgument),
+            ("schedule 1 20:00-07:00 1234567 90", InvalidArgument),
+            ("schedule 1 20:00-07:00 200000 -5", InvalidArgument),
+            ("schedule 1 20:00-07:00 200000 5000000", InvalidArgument),
+            ("schedule 1 20:00-07:00 200000 90 now", TooManyArguments),
+        ] {
+            assert_eq!(ConsoleCommand::parse(line), Err(error), "{}", line);
+        }
+    }
+
+    #[test]
+    fn lines() {
+        let mut console = Console::new();
+        assert_eq!(
+            feed(&mut console, b"help\r"),
+            Some(Ok(ConsoleCommand::Help))
+        );
+        // The LF of a CRLF is a blank line
+        assert_eq!(feed(&mut console, b"\n"), None);
+        assert_eq!(feed(&mut console, b"   \n"), None);
+        assert_eq!(
+            feed(&mut console, b"wake off\n"),
+            Some(Ok(ConsoleCommand::WakeTime(None)))
+        );
+        assert_eq!(
+            feed(&mut console, b"bogus\n"),
+            Some(Err(ConsoleError::UnknownCommand))
+        );
+    }
+
+    #[test]
+    fn backspace() {
+        let mut console = Console::new();
+        assert_eq!(
+            feed(&mut console, b"helpx\x08\n"),
+            Some(Ok(ConsoleCommand::Help))
+        );
+        assert_eq!(
+            feed(&mut console, b"hxx\x7f\x7felp\r"),
+            Some(Ok(ConsoleCommand::Help))
+        );
+        // Nothing to drop
+        assert_eq!(
+            feed(&mut console, b"\x08\x08help\n"),
+            Some(Ok(ConsoleCommand::Help))
+        );
+        assert_eq!(feed(&mut console, b"on\x08\x08\x08\n"), None);
+    }
+
+    #[test]
+    fn line_too_long_is_reported_once_at_the_end() {
+        let mut console = Console::new();
+        for _ in 0..100 {
+            assert_eq!(console.push(b'x'), None);
+        }
+        assert_eq!(console.push(b'\n'), Some(Err(ConsoleError::LineTooLong)));
+        assert_eq!(console.push(b'\n'), None);
+        assert_eq!(
+            feed(&mut console, b"help\n"),
+            Some(Ok(ConsoleCommand::Help))
+        );
+
+        // The longest line that fits
+        let mut line = [b' '; 65];
+        line[..4].copy_from_slice(b"help");
+        line[64] = b'\n';
+        assert_eq!(feed(&mut console, &line), Some(Ok(ConsoleCommand::Help)));
+    }
+
+    #[test]
+    fn invalid_character_is_reported_once_at_the_end() {
+        let mut console = Console::new();
+        assert_eq!(
+            feed(&mut console, b"h\x01elp\xff\n"),
+            Some(Err(ConsoleError::InvalidCharacter))
+        );
+        assert_eq!(console.push(b'\n'), None);
+        assert_eq!(
+            feed(&mut console, b"help\n"),
+            Some(Ok(ConsoleCommand::Help))
+        );
+
+        // The first error wins
+        let mut line = [b'x'; 70];
+        line[0] = 0x1b;
+        line[69] = b'\r';
+        assert_eq!(
+            feed(&mut console, &line),
+            Some(Err(ConsoleError::InvalidCharacter))
+        );
+    }
+}
@@ -1,177 +1,781 @@
 use crate::{
-    BasicColor, Button, Duration, InfallibleLedDriver, IrCommand, SystemClock, White, RGBW, RGBW8,
+    AutoOnAction, AutoOnLight, BasicColor, Button, Clock, ConfigError, ConsoleCommand,
+    ConsoleError, ControllerConfig, Duration, EffectId, Gesture, InfallibleLedDriver, IrAddress,
+    IrAddressFilter, IrCommand, IrFrame, IrGesture, IrGestureClassifier, IrLearner, Settings,
+    Speed, SunriseAlarm, TimeOfDay, White, MAX_SCHEDULE_WINDOWS, RGBW, RGBW8, STOCK_REMOTE,
 };
-use log::debug;
-use private::{Context, Events, StateMachine};
+use log::{debug, info, warn};
+use private::{Context, Events, StateMachine, States};
 
-// TODO
-// brightness handling
-//   - could do steps that turn on/off entire leds instead of adjusting per-led
+/// Brightness levels, stepped once per press and on every repeat frame once held.
+/// The max is full scale, so stepping up just saturates.
+/// Brightness is perceptual, anything lower than the min is practically off
+const BRIGHTNESS_MIN: u8 = 64;
+const BRIGHTNESS_MAX: u8 = u8::MAX;
+const BRIGHTNESS_STEP: u8 = 8;
 
-const AUTO_ON_DURATION: Duration = Duration::ONE_MINUTE;
-const MANUAL_ON_DURATION: Duration = Duration::ONE_MINUTE;
-//const AUTO_ON_DURATION: Duration = Duration::TEN_MINUTES;
-//const MANUAL_ON_DURATION: Duration = Duration::ONE_HOUR;
+/// Color used for AutoOn, and Button::On until another color is selected.
+/// Perceptual values, the LED output is r: 64, w: 128
+const DEFAULT_COLOR: RGBW8 = RGBW {
+    r: 146,
+    g: 0,
+    b: 0,
+    a: White(194),
+};
 
-const ONOFF_FADE_STEP_DURATION: Duration = Duration::from_millis(10);
+/// Color used for Button::White
+const WHITE_COLOR: RGBW8 = RGBW {
+    r: 0,
+    g: 0,
+    b: 0,
+    a: White(255),
+};
 
-const FLASH_MODE_STEP_DURATION: Duration = Duration::from_millis(5);
-const STROBE_MODE_STEP_DURATION: Duration = Duration::from_millis(5);
-const FADE_MODE_STEP_DURATION: Duration = Duration::from_millis(100);
-const SMOOTH_MODE_STEP_DURATION: Duration = Duration::from_millis(50);
+/// Color Mode::Sleep dims down to before turning off, a dim warm amber.
+/// Perceptual values like the other colors, each channel is capped at the current color
+const SLEEP_COLOR: RGBW8 = RGBW {
+    r: 96,
+    g: 28,
+    b: 0,
+    a: White(0),
+};
 
-/// Color used for AutoOn and Button::On
-const DEFAULT_COLOR: RGBW8 = RGBW {
-    r: 64,
+/// Color shown while learning the remote buttons that don't have one
+const LEARN_COLOR: RGBW8 = RGBW {
+    r: 0,
     g: 0,
-    b: 0,
-    a: White(128),
+    b: 96,
+    a: White(96),
 };
 
-pub struct Controller<LED: InfallibleLedDriver> {
-    sm: StateMachine<Context<LED>>,
+/// IR learning is cancelled after this long without a key
+const IR_LEARN_TIMEOUT: Duration = Duration::from_millis(30 * 1000);
+
+/// Modes are persisted as a byte, effects as their id offset by this
+const EFFECT_MODE_OFFSET: u8 = 0x10;
+
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub enum Mode {
+    AutoOn,
+    ManualOn,
+    /// Dims from the current color to `SLEEP_COLOR` over the sleep timer, then turns off
+    Sleep,
+    /// Ramps through `SUNRISE_COLORS` up to the wake time, stays on for the manual on duration
+    Sunrise,
+    /// Shows the color of the button being learned, see `IrLearner`
+    IrLearning,
+    /// One of the registered effects, see `Effect`
+    Effect(EffectId),
+}
+
+impl Mode {
+    /// Modes with a speed setting, see `AnimationSpeeds`
+    pub fn is_animated(self) -> bool {
+        matches!(self, Mode::Effect(_))
+    }
+
+    pub fn from_u8(value: u8) -> Option<Self> {
+        use Mode::*;
+        Some(match value {
+            0 => AutoOn,
+            1 => ManualOn,
+            2 => Sleep,
+            3 => Sunrise,
+            4 => IrLearning,
+            _ if value >= EFFECT_MODE_OFFSET => {
+                Effect(EffectId::from_u8(value - EFFECT_MODE_OFFSET)?)
+            }
+            _ => return None,
+        })
+    }
+}
+
+impl From<Mode> for u8 {
+    fn from(m: Mode) -> Self {
+        match m {
+            Mode::AutoOn => 0,
+            Mode::ManualOn => 1,
+            Mode::Sleep => 2,
+            Mode::Sunrise => 3,
+            Mode::IrLearning => 4,
+            Mode::Effect(id) => EFFECT_MODE_OFFSET + u8::from(id),
+        }
+    }
+}
+
+pub struct Controller<LED: InfallibleLedDriver, CLK: Clock> {
+    sm: StateMachine<Context<LED, CLK>>,
+    /// Whether the light was on when Button::On was last pressed, holding it then sleeps
+    on_pressed_while_on: bool,
+    sunrise: SunriseAlarm,
+    /// Last wall clock time seen, None until the RTC is set
+    time_of_day: Option<TimeOfDay>,
+    ir_learner: Option<IrLearner>,
+    ir_gestures: IrGestureClassifier,
 }
 
-impl<LED> Controller<LED>
+impl<LED, CLK> Controller<LED, CLK>
 where
     LED: InfallibleLedDriver,
+    CLK: Clock,
 {
-    pub fn new(driver: LED, sys_clock: &'static SystemClock) -> Self {
-        let mut sm = StateMachine::new(Context::new(driver, sys_clock));
+    pub fn new(driver: LED, clock: CLK, config: ControllerConfig) -> Result<Self, ConfigError> {
+        config.validate()?;
+        let mut sm = StateMachine::new(Context::new(driver, clock, config));
         sm.process_event(Events::Init).ok();
-        Controller { sm }
+        Ok(Controller {
+            sm,
+            on_pressed_while_on: false,
+            sunrise: SunriseAlarm::new(),
+            time_of_day: None,
+            ir_learner: None,
+            ir_gestures: IrGestureClassifier::new(),
+        })
+    }
+
+    pub fn config(&self) -> &ControllerConfig {
+        self.sm.context().config()
+    }
+
+    /// Takes effect on the next update, the current mode keeps running
+    pub fn set_config(&mut self, config: ControllerConfig) -> Result<(), ConfigError> {
+        config.validate()?;
+        debug!("Updated config {:?}", config);
+        self.sm.context_mut().set_config(config);
+        Ok(())
+    }
+
+    pub fn wake_time(&self) -> Option<TimeOfDay> {
+        self.config().wake_time
+    }
+
+    /// Sets or clears the sunrise alarm, seconds are dropped
+    pub fn set_wake_time(&mut self, wake_time: Option<TimeOfDay>) {
+        let wake_time = wake_time.and_then(|t| TimeOfDay::new(t.hours(), t.minutes(), 0));
+        debug!("Wake time {:?}", wake_time);
+        let mut config = *self.config();
+        config.wake_time = wake_time;
+        self.sm.context_mut().set_config(config);
+        self.sunrise = SunriseAlarm::new();
     }
 
     pub fn is_idle(&self) -> bool {
         match self.sm.state() {
-            private::States::Off(state_data) => state_data.borrow().destination_color_reached(),
+            States::Off(state_data) => state_data.borrow().destination_color_reached(),
             _ => false,
         }
     }
 
+    pub fn brightness(&self) -> u8 {
+        self.sm.context().brightness()
+    }
+
+    /// None if the mode isn't animated
+    pub fn speed(&self, mode: Mode) -> Option<Speed> {
+        self.sm.context().speeds().get(mode)
+    }
+
+    /// Takes effect on the next update if the mode is running, ignored if it isn't animated
+    pub fn set_speed(&mut self, mode: Mode, speed: Speed) {
+        if self.speed(mode) == Some(speed) || !mode.is_animated() {
+            return;
+        }
+        debug!("Speed ({:?}) {:?}", mode, speed);
+        let mut speeds = *self.sm.context().speeds();
+        speeds.set(mode, speed);
+        self.sm.context_mut().set_speeds(speeds);
+    }
+
+    /// Time left before Mode::Sleep turns the light off
+    pub fn sleep_remaining(&self) -> Option<Duration> {
+        match self.sm.state() {
+            States::On(state_data) if state_data.mode == Mode::Sleep => {
+                let elapsed = self.sm.context().elapsed_since(state_data.started_at);
+                Some(Duration::from_millis(
+                    state_data
+                        .on_duration
+                        .as_millis()
+                        .saturating_sub(elapsed.as_millis()),
+                ))
+            }
+            _ => None,
+        }
+    }
+
+    pub fn settings(&self) -> Settings {
+        let ctx = self.sm.context();
+        let (mode, color) = ctx.last_selection();
+        Settings {
+            mode,
+            color,
+            brightness: ctx.brightness(),
+            speeds: *ctx.speeds(),
+            config: *ctx.config(),
+        }
+    }
+
+    /// Restore saved settings, the light stays off until turned on
+    pub fn restore_settings(&mut self, settings: &Settings) -> Result<(), ConfigError> {
+        self.set_config(settings.config)?;
+        self.set_brightness(settings.brightness.max(BRIGHTNESS_MIN));
+        self.sm.context_mut().set_speeds(settings.speeds);
+        self.sm
+            .context_mut()
+            .set_last_selection(settings.mode, settings.color);
+        Ok(())
+    }
+
     /// Call this on a timer, 1~5 ms should do
     pub fn update(&mut self) {
         self.sm.process_event(Events::TimerCheck).ok();
+        if self.ir_learner.is_some() && self.mode() != Some(Mode::IrLearning) {
+            debug!("IR learning timed out");
+            self.ir_learner = None;
+        }
+        let now = self.sm.context().now();
+        if let Some(gesture) = self.ir_gestures.update(None, now) {
+            self.handle_ir_gesture(gesture);
+        }
+    }
+
+    /// Runs the effect for the manual on duration
+    pub fn run_effect(&mut self, id: EffectId) {
+        self.sm.process_event(Events::RunEffect(id)).ok();
     }
 
+    /// Gated by the schedule. Only turns the light on when off, or extends Mode::AutoOn,
+    /// so a color or mode the user selected isn't replaced by someone moving around
     pub fn handle_auto_on_event(&mut self) {
-        self.sm.process_event(Events::AutoOn).ok();
+        if !matches!(self.mode(), None | Some(Mode::AutoOn)) {
+            return;
+        }
+        let light = match self.config().schedule.auto_on_action(self.time_of_day) {
+            AutoOnAction::Suppress => {
+                debug!("AutoOn suppressed by the schedule");
+                return;
+            }
+            AutoOnAction::Default => AutoOnLight {
+                color: DEFAULT_COLOR,
+                duration: self.config().auto_on_duration,
+            },
+            AutoOnAction::Light(light) => light,
+        };
+        self.sm.process_event(Events::AutoOn(light)).ok();
+    }
+
+    /// Call this periodically with the wall clock time, drives the schedule and sunrise alarm
+    pub fn handle_time_of_day(&mut self, now: TimeOfDay) {
+        self.time_of_day = Some(now);
+        if let Some(wake_time) = self.wake_time() {
+            if let Some(until_wake) = self.sunrise.poll(wake_time, now) {
+                if !self.is_ir_learning() {
+                    self.sm.process_event(Events::Sunrise(until_wake)).ok();
+                }
+            }
+        }
+    }
+
+    pub fn is_ir_learning(&self) -> bool {
+        self.ir_learner.is_some()
+    }
+
+    /// Walks through the remote's buttons, the ring shows the color of the button
+    /// waiting for a key. The keymap is replaced once every button is done
+    pub fn start_ir_learning(&mut self) {
+        debug!("IR learning started");
+        self.ir_gestures.reset();
+        self.ir_learner = Some(IrLearner::new());
+        self.show_ir_learning();
+    }
+
+    /// The remotes the IR decoder should let through,
+    /// any remote while learning so a new one can be learned
+    pub fn ir_filter(&self) -> IrAddressFilter {
+        if self.is_ir_learning() {
+            IrAddressFilter::any()
+        } else {
+            self.config().ir_addresses
+        }
+    }
+
+    /// Maps the frame with the learned keymap into gestures, or hands it to the learning mode
+    pub fn handle_ir_frame(&mut self, frame: IrFrame) {
+        if let Some(learner) = self.ir_learner.as_mut() {
+            if learner.learn(frame) {
+                self.show_ir_learning();
+            }
+            return;
+        }
+        let cmd = self.config().ir_keymap.command(frame);
+        let now = self.sm.context().now();
+        if let Some(gesture) = self.ir_gestures.update(Some(cmd), now) {
+            self.handle_ir_gesture(gesture);
+        }
+    }
+
+    /// Buttons act on press, the brightness buttons keep stepping while held.
+    /// The remote has no spare button, holding On while already on starts the sleep timer,
+    /// holding it again in Mode::Sleep steps through the timer durations
+    pub fn handle_ir_gesture(&mut self, gesture: IrGesture) {
+        debug!("{:?}", gesture);
+        match gesture {
+            IrGesture::Press(button) => self.handle_ir_command(IrCommand {
+                button,
+                repeat: false,
+            }),
+            IrGesture::Hold(Button::On) if self.on_pressed_while_on => self.handle_on_held(),
+            IrGesture::Hold(button) | IrGesture::HoldRepeat(button) if button.repeat_allowed() => {
+                self.handle_ir_command(IrCommand {
+                    button,
+                    repeat: true,
+                })
+            }
+            _ => (),
+        }
     }
 
     pub fn handle_ir_command(&mut self, cmd: IrCommand) {
         let maybe_btn_color = BasicColor::from_button(cmd.button);
+        let maybe_btn_effect = EffectId::from_button(cmd.button);
         match cmd.button {
             Button::Off => {
                 self.sm.process_event(Events::ManualOff).ok();
             }
+            // A hold is its own gesture, see `handle_ir_gesture`
+            Button::On if cmd.repeat => (),
             Button::On => {
-                self.sm.process_event(Events::ManualOn(DEFAULT_COLOR)).ok();
+                self.on_pressed_while_on = self.mode().is_some();
+                if self.mode() != Some(Mode::Sleep) {
+                    self.resume();
+                }
             }
             Button::White => {
-                let color = RGBW8::new_alpha(0, 0, 0, White(255));
-                self.sm.process_event(Events::ManualOn(color)).ok();
+                self.sm.process_event(Events::ManualOn(WHITE_COLOR)).ok();
             }
             _btn if maybe_btn_color.is_some() => {
                 self.sm
                     .process_event(Events::ManualOn(maybe_btn_color.unwrap().as_rgbw()))
                     .ok();
             }
-            Button::Fade => {
-                self.sm.process_event(Events::Fade).ok();
-            }
-            Button::Strobe => {
-                self.sm.process_event(Events::Strobe).ok();
+            // Pressing the button of the running effect again steps its speed
+            _btn if maybe_btn_effect.is_some() => {
+                let id = maybe_btn_effect.unwrap();
+                if self.mode() == Some(Mode::Effect(id)) {
+                    self.step_speed();
+                } else {
+                    self.run_effect(id);
+                }
             }
-            Button::Smooth => {
-                self.sm.process_event(Events::Smooth).ok();
+            Button::BrightnessDown => {
+                let brightness = self
+                    .brightness()
+                    .saturating_sub(BRIGHTNESS_STEP)
+                    .max(BRIGHTNESS_MIN);
+                self.set_brightness(brightness);
             }
-            Button::Flash => {
-                self.sm.process_event(Events::Flash).ok();
+            Button::BrightnessUp => {
+                let brightness = self.brightness().saturating_add(BRIGHTNESS_STEP);
+                self.set_brightness(brightness);
             }
             _ => debug!("Ignoring {}", cmd),
         }
     }
+
+    /// Local push button controls, usable without the remote
+    pub fn handle_button_gesture(&mut self, gesture: Gesture) {
+        // While learning, a short press skips the button and a double press cancels
+        if let Some(learner) = self.ir_learner.as_mut() {
+            match gesture {
+                Gesture::ShortPress => {
+                    learner.skip();
+                    self.show_ir_learning();
+                }
+                Gesture::DoublePress => {
+                    debug!("IR learning cancelled");
+                    self.ir_learner = None;
+                    self.sm.process_event(Events::ManualOff).ok();
+                }
+                Gesture::LongPress => (),
+            }
+            return;
+        }
+        match gesture {
+            Gesture::ShortPress => {
+                if matches!(self.sm.state(), States::On(_)) {
+                    self.sm.process_event(Events::ManualOff).ok();
+                } else {
+                    self.resume();
+                }
+            }
+            // Cycles through the effects in registry order
+            Gesture::LongPress => {
+                let effects = EffectId::enumerate();
+                let next = match self.mode() {
+                    Some(Mode::Effect(id)) => effects.get(id as usize + 1),
+                    _ => None,
+                };
+                self.run_effect(*next.unwrap_or(&effects[0]));
+            }
+            Gesture::DoublePress => {
+                self.sm.process_event(Events::ManualOn(WHITE_COLOR)).ok();
+            }
+        }
+    }
+
+    /// `Time`, `LogLevel` and `Help` are up to the caller, they're ignored here.
+    /// Modes that can't be selected, like Mode::AutoOn, and schedule windows the config
+    /// doesn't allow are an invalid argument
+    pub fn handle_console_command(&mut self, cmd: ConsoleCommand) -> Result<(), ConsoleError> {
+        match cmd {
+            ConsoleCommand::On => {
+                if !matches!(self.sm.state(), States::On(_)) {
+                    self.resume();
+                }
+            }
+            ConsoleCommand::Off => {
+                self.sm.process_event(Events::ManualOff).ok();
+            }
+            ConsoleCommand::Color(color) => {
+                self.sm.process_event(Events::ManualOn(color)).ok();
+            }
+            // Like holding Button::On, the sleep timer only starts while on
+            ConsoleCommand::Mode(Mode::Sleep) => {
+                self.sm.process_event(Events::Sleep).ok();
+            }
+            ConsoleCommand::Mode(Mode::IrLearning) => self.start_ir_learning(),
+            ConsoleCommand::Mode(Mode::Effect(id)) => self.run_effect(id),
+            ConsoleCommand::Mode(_) => return Err(ConsoleError::InvalidArgument),
+            ConsoleCommand::WakeTime(wake_time) => self.set_wake_time(wake_time),
+            ConsoleCommand::Schedule(index, window) => {
+                let mut config = *self.config();
+                config.schedule.set_window(index, window);
+                self.set_config(config).map_err(|e| {
+                    warn!("Schedule window not set {:?}", e);
+                    ConsoleError::InvalidArgument
+                })?;
+            }
+            ConsoleCommand::IrAllow(address) => {
+                let mut config = *self.config();
+                if !config.ir_addresses.add(address) {
+                    warn!("No room to accept IR address {:?}", address);
+                }
+                self.sm.context_mut().set_config(config);
+            }
+            ConsoleCommand::IrDeny(address) => {
+                let mut config = *self.config();
+                config.ir_addresses.remove(address);
+                self.sm.context_mut().set_config(config);
+            }
+            ConsoleCommand::IrAny(any) => {
+                let mut config = *self.config();
+                config.ir_addresses.set_any(any);
+                self.sm.context_mut().set_config(config);
+            }
+            ConsoleCommand::Status => self.log_status(),
+            ConsoleCommand::Time(_) | ConsoleCommand::LogLevel(_) | ConsoleCommand::Help => (),
+        }
+        Ok(())
+    }
+
+    fn log_status(&self) {
+        let (last_mode, last_color) = self.sm.context().last_selection();
+        info!(
+            "Mode {:?}, speed {:?}, brightness {}, sleep remaining {:?}",
+            self.mode(),
+            self.mode().and_then(|m| self.speed(m)),
+            self.brightness(),
+            self.sleep_remaining()
+        );
+        info!("Last selection {:?} {:?}", last_mode, last_color);
+        info!(
+            "Time of day {:?}, wake time {:?}",
+            self.time_of_day,
+            self.wake_time()
+        );
+        for index in 0..MAX_SCHEDULE_WINDOWS {
+            if let Some(window) = self.config().schedule.window(index) {
+                info!("Schedule {} {:?}", index + 1, window);
+            }
+        }
+        info!("IR allow any {}", self.config().ir_addresses.is_any());
+        for address in self.config().ir_addresses.addresses() {
+            info!("IR allow {:?}", address);
+        }
+    }
+
+    /// Each button fades in from off, so every key taken is seen
+    fn show_ir_learning(&mut self) {
+        let button = match self.ir_learner.and_then(|l| l.button()) {
+            Some(button) => button,
+            None => return self.finish_ir_learning(),
+        };
+        debug!("Press the key for {}", button);
+        let color = match button {
+            Button::White => WHITE_COLOR,
+            b => BasicColor::from_button(b)
+                .map(BasicColor::as_rgbw)
+                .unwrap_or(LEARN_COLOR),
+        };
+        self.sm.process_event(Events::IrLearn(color)).ok();
+    }
+
+    fn finish_ir_learning(&mut self) {
+        if let Some(learner) = self.ir_learner.take() {
+            debug!("IR learning done {:?}", learner.keymap());
+            let mut config = *self.config();
+            config.ir_keymap = *learner.keymap();
+            let learned = config.ir_keymap.keys().map(IrAddress::of);
+            // Skipping every button went back to the stock remote
+            let stock = config.ir_keymap.is_empty().then_some(STOCK_REMOTE);
+            for address in learned.chain(stock) {
+                if !config.ir_addresses.add(address) {
+                    warn!("No room to accept IR address {:?}", address);
+                }
+            }
+            self.sm.context_mut().set_config(config);
+            self.sm.process_event(Events::ManualOff).ok();
+        }
+    }
+
+    fn set_brightness(&mut self, brightness: u8) {
+        self.sm.context_mut().set_brightness(brightness);
+    }
+
+    fn handle_on_held(&mut self) {
+        if self.mode() == Some(Mode::Sleep) {
+            let mut config = *self.config();
+            config.sleep_timer = config.sleep_timer.next();
+            self.sm.context_mut().set_config(config);
+        }
+        self.sm.process_event(Events::Sleep).ok();
+    }
+
+    /// Steps the speed of the running mode, wrapping around
+    fn step_speed(&mut self) {
+        if let Some(mode) = self.mode() {
+            if let Some(speed) = self.speed(mode) {
+                self.set_speed(mode, speed.next());
+            }
+        }
+    }
+
+    /// Current mode, None when off
+    fn mode(&self) -> Option<Mode> {
+        match self.sm.state() {
+            States::On(state_data) => Some(state_data.mode),
+            _ => None,
+        }
+    }
+
+    /// Turn on with the last mode and color selected
+    fn resume(&mut self) {
+        let event = match self.sm.context().last_selection() {
+            (Mode::Effect(id), _) => Events::RunEffect(id),
+            (_, color) => Events::ManualOn(color),
+        };
+        self.sm.process_event(event).ok();
+    }
 }
 
 mod private {
-    use super::{
-        AUTO_ON_DURATION, DEFAULT_COLOR, FADE_MODE_STEP_DURATION, FLASH_MODE_STEP_DURATION,
-        MANUAL_ON_DURATION, ONOFF_FADE_STEP_DURATION, SMOOTH_MODE_STEP_DURATION,
-        STROBE_MODE_STEP_DURATION,
-    };
+    use super::{Mode, BRIGHTNESS_MAX, DEFAULT_COLOR, IR_LEARN_TIMEOUT, SLEEP_COLOR};
     use crate::{
-        BasicColor, FadeOffRgbw, FadeToRgbw, InfallibleLedDriver, Instant, RandomColorGen,
-        SystemClock, COLOR_OFF, RGBW8,
+        next_sunrise_color, sunrise_step_duration, AnimationSpeeds, AutoOnLight, Clock,
+        ControllerConfig, Duration, Effect, EffectContext, EffectId, EffectState, FadeOffRgbw,
+        FadeToRgbw, Frame, InfallibleLedDriver, Instant, Interpolation, RandomColorGen, White,
+        COLOR_OFF, FRAME_OFF, MAX_NUM_LEDS, RGBW, RGBW8, SUNRISE_COLORS,
     };
     use core::cell::RefCell;
     use log::debug;
     use smlang::statemachine;
 
-    #[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
-    pub enum Mode {
-        AutoOn,
-        ManualOn,
-        Fade,
-        Strobe,
-        Smooth,
-        Flash,
-    }
-
     statemachine! {
         *Reset + Init / init_action = Off,
 
-        Off(OffStateData) + AutoOn / off_to_auto_on_action = On,
+        Off(OffStateData) + AutoOn(AutoOnLight) / off_to_auto_on_action = On,
         Off(OffStateData) + ManualOn(RGBW8) / off_to_manual_on_action = On,
-        Off(OffStateData) + Fade / off_to_fade_on_action = On,
-        Off(OffStateData) + Strobe / off_to_strobe_on_action = On,
-        Off(OffStateData) + Smooth / off_to_smooth_on_action = On,
-        Off(OffStateData) + Flash / off_to_flash_on_action = On,
+        Off(OffStateData) + RunEffect(EffectId) / off_to_effect_on_action = On,
+        Off(OffStateData) + Sunrise(Duration) / off_to_sunrise_on_action = On,
+        Off(OffStateData) + IrLearn(RGBW8) / off_to_ir_learn_on_action = On,
         Off(OffStateData) + TimerCheck [off_timer_check_guard] / off_to_off_action = Off,
 
         On(OnStateData) + ManualOff / on_to_off_action = Off,
         On(OnStateData) + TimerCheck [on_timer_check_guard] / on_to_off_action = Off,
 
         On(OnStateData) + ManualOn(RGBW8) / on_to_manual_on_action = On,
-        On(OnStateData) + AutoOn / on_to_auto_on_action = On,
-        On(OnStateData) + Fade / on_to_fade_on_action = On,
-        On(OnStateData) + Strobe / on_to_strobe_on_action = On,
-        On(OnStateData) + Smooth / on_to_smooth_on_action = On,
-        On(OnStateData) + Flash / on_to_flash_on_action = On,
+        On(OnStateData) + AutoOn(AutoOnLight) / on_to_auto_on_action = On,
+        On(OnStateData) + RunEffect(EffectId) / on_to_effect_on_action = On,
+        On(OnStateData) + Sleep / on_to_sleep_on_action = On,
+        On(OnStateData) + Sunrise(Duration) / on_to_sunrise_on_action = On,
+        On(OnStateData) + IrLearn(RGBW8) / on_to_ir_learn_on_action = On,
     }
 
-    pub struct Context<LED> {
+    pub struct Context<LED, CLK> {
         driver: LED,
         color_gen: RandomColorGen,
-        clock: &'static SystemClock,
+        clock: CLK,
+        config: ControllerConfig,
+        brightness: u8,
+        speeds: AnimationSpeeds,
+        /// Last mode and color selected by the user, AutoOn doesn't count
+        last_mode: Mode,
+        last_color: RGBW8,
+        /// Last frame rendered, kept so it can be re-drawn
+        frame: Frame,
     }
 
-    impl<LED> Context<LED>
+    impl<LED, CLK> Context<LED, CLK>
     where
         LED: InfallibleLedDriver,
+        CLK: Clock,
     {
-        pub fn new(driver: LED, clock: &'static SystemClock) -> Self {
+        pub fn new(mut driver: LED, clock: CLK, config: ControllerConfig) -> Self {
+            debug_assert!(LED::NUM_LEDS <= MAX_NUM_LEDS);
+            driver.set_brightness(BRIGHTNESS_MAX);
             Context {
                 driver,
                 color_gen: RandomColorGen::new(clock.now().as_millis() as _),
                 clock,
+                config,
+                brightness: BRIGHTNESS_MAX,
+                speeds: AnimationSpeeds::default(),
+                last_mode: Mode::ManualOn,
+                last_color: DEFAULT_COLOR,
+                frame: FRAME_OFF,
             }
         }
 
-        fn next_rand_rgb(&mut self, current_color: RGBW8) -> RGBW8 {
-            loop {
-                let next = self.color_gen.rand_rgb();
-                if next != current_color {
-                    break next;
-                }
+        pub fn config(&self) -> &ControllerConfig {
+            &self.config
+        }
+
+        pub fn set_config(&mut self, config: ControllerConfig) {
+            self.config = config;
+        }
+
+        pub fn brightness(&self) -> u8 {
+            self.brightness
+        }
+
+        pub fn speeds(&self) -> &AnimationSpeeds {
+            &self.speeds
+        }
+
+        pub fn set_speeds(&mut self, speeds: AnimationSpeeds) {
+            self.speeds = speeds;
+        }
+
+        pub fn last_selection(&self) -> (Mode, RGBW8) {
+            (self.last_mode, self.last_color)
+        }
+
+        pub fn now(&self) -> Instant {
+            self.clock.now()
+        }
+
+        pub fn elapsed_since(&self, earlier: Instant) -> Duration {
+            self.clock.duration_since(earlier)
+        }
+
+        pub fn set_last_selection(&mut self, mode: Mode, color: RGBW8) {
+            if !matches!(
+                mode,
+                Mode::AutoOn | Mode::Sleep | Mode::Sunrise | Mode::IrLearning
+            ) {
+                self.last_mode = mode;
+                self.last_color = color;
             }
         }
 
-        fn next_rand_color(&mut self, current_color: RGBW8) -> BasicColor {
-            loop {
-                let next = self.color_gen.rand_color();
-                if next.as_rgbw() != current_color {
-                    break next;
-                }
+        pub fn set_brightness(&mut self, brightness: u8) {
+            if brightness != self.brightness {
+                debug!("Brightness {}", brightness);
+                self.brightness = brightness;
+                self.driver.set_brightness(brightness);
+                self.write_frame();
+            }
+        }
+
+        /// Render a single color to every pixel
+        fn render_color(&mut self, color: &RGBW8) {
+            self.frame.iter_mut().for_each(|p| *p = *color);
+            self.write_frame();
+        }
+
+        /// Render the effect's frame, only written out if it changed.
+        /// The first pixel becomes the color to fade from
+        fn render_effect(&mut self, run: &mut EffectRun) {
+            let prev_frame = self.frame;
+            run.effect.render(&mut self.frame[..LED::NUM_LEDS]);
+            if self.frame != prev_frame {
+                self.write_frame();
+            }
+            run.color = self.frame[0];
+        }
+
+        fn write_frame(&mut self) {
+            self.driver.set_frame(&self.frame[..LED::NUM_LEDS]);
+        }
+
+        fn effect_context(&mut self, id: EffectId) -> EffectContext<'_> {
+            EffectContext {
+                now: self.clock.now(),
+                config: &self.config,
+                speed: self.speeds.get(Mode::Effect(id)).unwrap_or_default(),
+                rng: &mut self.color_gen,
+            }
+        }
+
+        fn fade_duration(&self, mode: Mode) -> Duration {
+            match mode {
+                Mode::Sleep => self.config.sleep_timer.duration(),
+                // Sunrise is set from the time left until the wake time, see enter_sunrise
+                _ => self.config.onoff_fade_duration,
+            }
+        }
+
+        fn on_duration(&self, mode: Mode) -> Duration {
+            match mode {
+                Mode::AutoOn => self.config.auto_on_duration,
+                // The fade is the timer, turns off once it reaches SLEEP_COLOR
+                Mode::Sleep => self.config.sleep_timer.duration(),
+                Mode::IrLearning => IR_LEARN_TIMEOUT,
+                _ => self.config.manual_on_duration,
+            }
+        }
+
+        fn enter_auto_on(&mut self, current_color: RGBW8, light: &AutoOnLight) -> OnStateData {
+            let mut state_data = self.common_enter_on(Mode::AutoOn, current_color, light.color);
+            state_data.on_duration = light.duration;
+            state_data
+        }
+
+        /// Ramps up, then stays on like a manual selection
+        fn enter_sunrise(&mut self, current_color: RGBW8, until_wake: Duration) -> OnStateData {
+            let mut state_data =
+                self.common_enter_on(Mode::Sunrise, current_color, SUNRISE_COLORS[0]);
+            if let Animation::Fade(fade_to) = &mut *state_data.animation.borrow_mut() {
+                fade_to.duration = sunrise_step_duration(until_wake);
+            }
+            state_data.on_duration = until_wake + self.config.manual_on_duration;
+            state_data
+        }
+
+        fn enter_effect(&mut self, id: EffectId, current_color: RGBW8) -> OnStateData {
+            let mode = Mode::Effect(id);
+            debug!("Entered On ({:?})", mode);
+            self.last_mode = mode;
+            let mut run = EffectRun {
+                effect: id.effect(),
+                color: current_color,
+            };
+            run.effect.init(&mut self.effect_context(id), current_color);
+            self.render_effect(&mut run);
+            OnStateData {
+                mode,
+                started_at: self.clock.now(),
+                on_duration: self.on_duration(mode),
+                animation: RefCell::new(Animation::Effect(run)),
             }
         }
 
@@ -182,30 +786,46 @@ mod private {
             destination_color: RGBW8,
         ) -> OnStateData {
             debug!("Entered On ({:?}) {:?}", mode, destination_color);
+            self.set_last_selection(mode, destination_color);
+            let now = self.clock.now();
             OnStateData {
                 mode,
-                started_at: self.clock.now(),
-                fade_to: FadeToState::new_refcell(
+                started_at: now,
+                on_duration: self.on_duration(mode),
+                animation: RefCell::new(Animation::Fade(FadeToState::new(
                     current_color,
                     destination_color,
-                    self.clock.now(),
-                ),
+                    now,
+                    self.fade_duration(mode),
+                    Interpolation::Rgb,
+                ))),
             }
         }
     }
 
-    impl<LED> StateMachineContext for Context<LED>
+    impl<LED, CLK> StateMachineContext for Context<LED, CLK>
     where
         LED: InfallibleLedDriver,
+        CLK: Clock,
     {
         fn init_action(&mut self) -> OffStateData {
             debug!("Initialized LED controller state machine");
-            self.driver.set_off();
-            FadeToState::new_refcell(COLOR_OFF, COLOR_OFF, self.clock.now())
+            self.render_color(&COLOR_OFF);
+            FadeToState::new_refcell(
+                COLOR_OFF,
+                COLOR_OFF,
+                self.clock.now(),
+                self.config.onoff_fade_duration,
+                Interpolation::Rgb,
+            )
         }
 
-        fn off_to_auto_on_action(&mut self, state_data: &OffStateData) -> OnStateData {
-            self.common_enter_on(Mode::AutoOn, state_data.borrow().color, DEFAULT_COLOR)
+        fn off_to_auto_on_action(
+            &mut self,
+            state_data: &OffStateData,
+            event_data: &AutoOnLight,
+        ) -> OnStateData {
+            self.enter_auto_on(state_data.borrow().color, event_data)
         }
 
         fn off_to_manual_on_action(
@@ -216,49 +836,49 @@ mod private {
             self.common_enter_on(Mode::ManualOn, state_data.borrow().color, *event_data)
         }
 
-        fn off_to_fade_on_action(&mut self, state_data: &OffStateData) -> OnStateData {
-            let current_color = state_data.borrow().color;
-            let next_color = self.next_rand_rgb(current_color);
-            self.common_enter_on(Mode::Fade, current_color, next_color)
-        }
-
-        fn off_to_strobe_on_action(&mut self, state_data: &OffStateData) -> OnStateData {
-            let current_color = state_data.borrow().color;
-            let next_color = self.next_rand_rgb(current_color);
-            self.common_enter_on(Mode::Strobe, current_color, next_color)
+        fn off_to_effect_on_action(
+            &mut self,
+            state_data: &OffStateData,
+            event_data: &EffectId,
+        ) -> OnStateData {
+            self.enter_effect(*event_data, state_data.borrow().color)
         }
 
-        fn off_to_smooth_on_action(&mut self, state_data: &OffStateData) -> OnStateData {
-            let current_color = state_data.borrow().color;
-            let next_color = self.next_rand_color(current_color).as_rgbw();
-            self.common_enter_on(Mode::Smooth, current_color, next_color)
+        fn off_to_sunrise_on_action(
+            &mut self,
+            state_data: &OffStateData,
+            event_data: &Duration,
+        ) -> OnStateData {
+            self.enter_sunrise(state_data.borrow().color, *event_data)
         }
 
-        fn off_to_flash_on_action(&mut self, state_data: &OffStateData) -> OnStateData {
-            let current_color = state_data.borrow().color;
-            let next_color = self.next_rand_color(current_color).as_rgbw();
-            self.common_enter_on(Mode::Flash, current_color, next_color)
+        fn off_to_ir_learn_on_action(
+            &mut self,
+            _state_data: &OffStateData,
+            event_data: &RGBW8,
+        ) -> OnStateData {
+            self.common_enter_on(Mode::IrLearning, COLOR_OFF, *event_data)
         }
 
         fn off_to_off_action(&mut self, state_data: &OffStateData) -> OffStateData {
-            FadeToState::new_refcell(state_data.borrow().color, COLOR_OFF, self.clock.now())
+            FadeToState::new_refcell(
+                state_data.borrow().color,
+                COLOR_OFF,
+                self.clock.now(),
+                self.config.onoff_fade_duration,
+                Interpolation::Rgb,
+            )
         }
 
         fn off_timer_check_guard(&mut self, state_data: &OffStateData) -> bool {
             if !state_data.borrow().color.is_off() {
-                let dur_since = self
-                    .clock
-                    .duration_since(state_data.borrow().transitioned_at);
-
-                if dur_since >= ONOFF_FADE_STEP_DURATION {
-                    state_data.borrow_mut().transitioned_at = self.clock.now();
-                    state_data.borrow_mut().color.step_down();
-                    self.driver.set_pixels(&state_data.borrow().color);
+                if state_data.borrow_mut().update(self.clock.now()) {
+                    self.render_color(&state_data.borrow().color);
                 }
 
                 if state_data.borrow().color.is_off() {
                     debug!("Re-seed PRNG");
-                    self.driver.set_off();
+                    self.render_color(&COLOR_OFF);
                     self.color_gen = RandomColorGen::new(self.clock.now().as_millis() as _);
                 }
             }
@@ -273,98 +893,92 @@ mod private {
         ) -> OnStateData {
             self.common_enter_on(
                 Mode::ManualOn,
-                state_data.fade_to.borrow().color,
+                state_data.animation.borrow().color(),
                 *event_data,
             )
         }
 
-        fn on_to_auto_on_action(&mut self, state_data: &OnStateData) -> OnStateData {
-            self.common_enter_on(
-                Mode::AutoOn,
-                state_data.fade_to.borrow().color,
-                DEFAULT_COLOR,
-            )
+        fn on_to_auto_on_action(
+            &mut self,
+            state_data: &OnStateData,
+            event_data: &AutoOnLight,
+        ) -> OnStateData {
+            self.enter_auto_on(state_data.animation.borrow().color(), event_data)
         }
 
-        fn on_to_fade_on_action(&mut self, state_data: &OnStateData) -> OnStateData {
-            let current_color = state_data.fade_to.borrow().color;
-            let next_color = self.next_rand_rgb(current_color);
-            self.common_enter_on(Mode::Fade, current_color, next_color)
+        fn on_to_effect_on_action(
+            &mut self,
+            state_data: &OnStateData,
+            event_data: &EffectId,
+        ) -> OnStateData {
+            let current_color = state_data.animation.borrow().color();
+            self.enter_effect(*event_data, current_color)
         }
 
-        fn on_to_strobe_on_action(&mut self, state_data: &OnStateData) -> OnStateData {
-            let current_color = state_data.fade_to.borrow().color;
-            let next_color = self.next_rand_rgb(current_color);
-            self.common_enter_on(Mode::Strobe, current_color, next_color)
+        fn on_to_sleep_on_action(&mut self, state_data: &OnStateData) -> OnStateData {
+            debug!("Sleep timer {:?}", self.config.sleep_timer);
+            let current_color = state_data.animation.borrow().color();
+            // Only ever dims, a channel already below SLEEP_COLOR isn't brought up to it
+            let cap = |sleep: u8, current: u8| sleep.min(current);
+            let sleep_color = RGBW {
+                r: cap(SLEEP_COLOR.r, current_color.r),
+                g: cap(SLEEP_COLOR.g, current_color.g),
+                b: cap(SLEEP_COLOR.b, current_color.b),
+                a: White(cap(SLEEP_COLOR.a.0, current_color.a.0)),
+            };
+            self.common_enter_on(Mode::Sleep, current_color, sleep_color)
         }
 
-        fn on_to_smooth_on_action(&mut self, state_data: &OnStateData) -> OnStateData {
-            let current_color = state_data.fade_to.borrow().color;
-            let next_color = self.next_rand_color(current_color).as_rgbw();
-            self.common_enter_on(Mode::Smooth, current_color, next_color)
+        fn on_to_sunrise_on_action(
+            &mut self,
+            state_data: &OnStateData,
+            event_data: &Duration,
+        ) -> OnStateData {
+            self.enter_sunrise(state_data.animation.borrow().color(), *event_data)
         }
 
-        fn on_to_flash_on_action(&mut self, state_data: &OnStateData) -> OnStateData {
-            let current_color = state_data.fade_to.borrow().color;
-            let next_color = self.next_rand_color(current_color).as_rgbw();
-            self.common_enter_on(Mode::Flash, current_color, next_color)
+        fn on_to_ir_learn_on_action(
+            &mut self,
+            _state_data: &OnStateData,
+            event_data: &RGBW8,
+        ) -> OnStateData {
+            self.common_enter_on(Mode::IrLearning, COLOR_OFF, *event_data)
         }
 
         fn on_to_off_action(&mut self, state_data: &OnStateData) -> OffStateData {
             debug!("Entered Off");
             FadeToState::new_refcell(
-                state_data.fade_to.borrow().color,
+                state_data.animation.borrow().color(),
                 COLOR_OFF,
                 self.clock.now(),
+                self.config.onoff_fade_duration,
+                Interpolation::Rgb,
             )
         }
 
         fn on_timer_check_guard(&mut self, state_data: &OnStateData) -> bool {
-            let dest_color_reached = state_data.fade_to.borrow().destination_color_reached();
-
-            if !dest_color_reached {
-                let dur_since = self
-                    .clock
-                    .duration_since(state_data.fade_to.borrow().transitioned_at);
-
-                let should_step = match state_data.mode {
-                    Mode::AutoOn | Mode::ManualOn => dur_since >= ONOFF_FADE_STEP_DURATION,
-                    Mode::Fade => dur_since >= FADE_MODE_STEP_DURATION,
-                    Mode::Strobe => dur_since >= STROBE_MODE_STEP_DURATION,
-                    Mode::Smooth => dur_since >= SMOOTH_MODE_STEP_DURATION,
-                    Mode::Flash => dur_since >= FLASH_MODE_STEP_DURATION,
-                };
-
-                if should_step {
-                    let mut f = state_data.fade_to.borrow_mut();
-                    f.transitioned_at = self.clock.now();
-                    f.step_color_to();
-                    self.driver.set_pixels(&f.color);
-                }
+            let now = self.clock.now();
 
-                if state_data.fade_to.borrow().destination_color_reached() {
-                    let current_color = state_data.fade_to.borrow().color;
-                    match state_data.mode {
-                        Mode::Fade | Mode::Strobe => {
-                            let next_color = self.next_rand_rgb(current_color);
-                            debug!("Next color ({:?}) {:?}", state_data.mode, next_color);
-                            state_data.fade_to.borrow_mut().destination_color = next_color;
-                        }
-                        Mode::Smooth | Mode::Flash => {
-                            let next_color = self.next_rand_color(current_color).as_rgbw();
+            match &mut *state_data.animation.borrow_mut() {
+                Animation::Fade(fade_to) => {
+                    if fade_to.update(now) {
+                        self.render_color(&fade_to.color);
+                    }
+                    if state_data.mode == Mode::Sunrise && fade_to.destination_color_reached() {
+                        if let Some(next_color) = next_sunrise_color(&fade_to.destination_color) {
                             debug!("Next color ({:?}) {:?}", state_data.mode, next_color);
-                            state_data.fade_to.borrow_mut().destination_color = next_color;
+                            fade_to.fade_to(next_color, now);
                         }
-                        _ => (),
+                    }
+                }
+                Animation::Effect(run) => {
+                    if run.effect.tick(&mut self.effect_context(run.effect.id())) {
+                        self.render_effect(run);
                     }
                 }
             }
 
-            if state_data.mode == Mode::AutoOn {
-                self.clock.duration_since(state_data.started_at) >= AUTO_ON_DURATION
-            } else {
-                self.clock.duration_since(state_data.started_at) >= MANUAL_ON_DURATION
-            }
+            self.clock.duration_since(state_data.started_at) >= state_data.on_duration
         }
     }
 
@@ -372,33 +986,106 @@ mod private {
     pub struct OnStateData {
         pub mode: Mode,
         pub started_at: Instant,
-        pub fade_to: RefCell<FadeToState>,
+        /// Turns off once on this long
+        pub on_duration: Duration,
+        pub animation: RefCell<Animation>,
     }
 
     pub type OffStateData = RefCell<FadeToState>;
 
+    /// Per-mode timing state
+    #[derive(Copy, Clone, PartialEq, Debug)]
+    pub enum Animation {
+        /// Fades into the destination, Mode::Sunrise then picks the next one
+        Fade(FadeToState),
+        Effect(EffectRun),
+    }
+
+    impl Animation {
+        /// The color currently shown
+        pub fn color(&self) -> RGBW8 {
+            match self {
+                Animation::Fade(s) => s.color,
+                Animation::Effect(s) => s.color,
+            }
+        }
+    }
+
+    /// A running effect, `color` is the first pixel, used to fade off from
+    #[derive(Copy, Clone, PartialEq, Debug)]
+    pub struct EffectRun {
+        pub effect: EffectState,
+        pub color: RGBW8,
+    }
+
+    /// Fade from a start color to a destination color over a duration
     #[derive(Copy, Clone, PartialEq, Debug)]
     pub struct FadeToState {
         pub color: RGBW8,
+        pub start_color: RGBW8,
         pub destination_color: RGBW8,
-        pub transitioned_at: Instant,
+        pub started_at: Instant,
+        pub duration: Duration,
+        pub interpolation: Interpolation,
     }
 
     impl FadeToState {
+        fn new(
+            color: RGBW8,
+            destination_color: RGBW8,
+            started_at: Instant,
+            duration: Duration,
+            interpolation: Interpolation,
+        ) -> Self {
+            FadeToState {
+                color,
+                start_color: color,
+                destination_color,
+                started_at,
+                duration,
+                interpolation,
+            }
+        }
+
         fn new_refcell(
             color: RGBW8,
             destination_color: RGBW8,
-            transitioned_at: Instant,
+            started_at: Instant,
+            duration: Duration,
+            interpolation: Interpolation,
         ) -> RefCell<Self> {
-            RefCell::new(FadeToState {
+            RefCell::new(Self::new(
                 color,
                 destination_color,
-                transitioned_at,
-            })
+                started_at,
+                duration,
+                interpolation,
+            ))
         }
 
-        fn step_color_to(&mut self) {
-            self.color.step_to(&self.destination_color);
+        /// Restart the fade from the current color, keeps the duration
+        fn fade_to(&mut self, destination_color: RGBW8, now: Instant) {
+            self.start_color = self.color;
+            self.destination_color = destination_color;
+            self.started_at = now;
+        }
+
+        /// Returns true if the color changed
+        fn update(&mut self, now: Instant) -> bool {
+            let prev_color = self.color;
+            let elapsed = now.duration_since(self.started_at);
+            self.color = match self.interpolation {
+                Interpolation::Rgb => {
+                    self.start_color
+                        .interpolate(&self.destination_color, elapsed, self.duration)
+                }
+                Interpolation::Hsv => self.start_color.interpolate_hsv(
+                    &self.destination_color,
+                    elapsed,
+                    self.duration,
+                ),
+            };
+            self.color != prev_color
         }
 
         pub fn destination_color_reached(&self) -> bool {
@@ -406,3 +1093,805 @@ mod private {
         }
     }
 }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        Duration, Frame, IrKey, IrProtocol, MockClock, ScheduleWindow, SleepTimer, Strobe,
+        TheaterChase, COLOR_OFF, FRAME_OFF, IR_RELEASE_TIMEOUT, MAX_NUM_LEDS, NUM_BUTTONS,
+    };
+    use core::cell::Cell;
+
+    const BLUE: RGBW8 = RGBW {
+        r: 0,
+        g: 0,
+        b: 255,
+        a: White(0),
+    };
+
+    /// Keeps the last frame written
+    struct RecordingDriver<'a>(&'a Cell<Frame>);
+
+    impl<'a> InfallibleLedDriver for RecordingDriver<'a> {
+        const NUM_LEDS: usize = MAX_NUM_LEDS;
+
+        fn set_brightness(&mut self, _brightness: u8) {}
+
+        fn write_pixels<I>(&mut self, pixels: I)
+        where
+            I: Iterator<Item = RGBW8>,
+        {
+            let mut frame = FRAME_OFF;
+            frame.iter_mut().zip(pixels).for_each(|(p, c)| *p = c);
+            self.0.set(frame);
+        }
+    }
+
+    /// Runs the controller updates for `duration`, 5 ms apart like the firmware
+    fn run<LED: InfallibleLedDriver>(
+        controller: &mut Controller<LED, &MockClock>,
+        clock: &MockClock,
+        duration: Duration,
+    ) {
+        for _ in 0..duration.as_millis() / 5 {
+            clock.advance(Duration::from_millis(5));
+            controller.update();
+        }
+    }
+
+    fn press<LED: InfallibleLedDriver>(
+        controller: &mut Controller<LED, &MockClock>,
+        button: Button,
+    ) {
+        controller.handle_ir_command(IrCommand {
+            button,
+            repeat: false,
+        });
+    }
+
+    fn hold_on<LED: InfallibleLedDriver>(controller: &mut Controller<LED, &MockClock>) {
+        controller.handle_ir_gesture(IrGesture::Press(Button::On));
+        controller.handle_ir_gesture(IrGesture::Hold(Button::On));
+    }
+
+    /// A stock remote frame then its repeat frames, 108 ms apart like NEC, then the release
+    fn send<LED: InfallibleLedDriver>(
+        controller: &mut Controller<LED, &MockClock>,
+        clock: &MockClock,
+        button: Button,
+        repeats: u32,
+    ) {
+        let key = IrKey {
+            protocol: IrProtocol::Nec,
+            address: STOCK_REMOTE.address,
+            command: (0..=u8::MAX)
+                .find(|c| Button::from_stock_command(*c) == button)
+                .unwrap(),
+        };
+        controller.handle_ir_frame(IrFrame { key, repeat: false });
+        for _ in 0..repeats {
+            run(controller, clock, Duration::from_millis(108));
+            controller.handle_ir_frame(IrFrame { key, repeat: true });
+        }
+        run(controller, clock, IR_RELEASE_TIMEOUT);
+    }
+
+    #[test]
+    fn auto_on_expires() {
+        let frame = Cell::new(FRAME_OFF);
+        let clock = MockClock::new();
+        let config = ControllerConfig::default();
+        let mut controller = Controller::new(RecordingDriver(&frame), &clock, config).unwrap();
+        assert!(controller.is_idle());
+
+        controller.handle_auto_on_event();
+        run(&mut controller, &clock, config.onoff_fade_duration);
+        assert_eq!(controller.mode(), Some(Mode::AutoOn));
+        assert_eq!(frame.get(), [DEFAULT_COLOR; MAX_NUM_LEDS]);
+
+        let remaining =
+            config.auto_on_duration.as_millis() - config.onoff_fade_duration.as_millis();
+        run(
+            &mut controller,
+            &clock,
+            Duration::from_millis(remaining - 5),
+        );
+        assert_eq!(controller.mode(), Some(Mode::AutoOn));
+
+        run(&mut controller, &clock, Duration::from_millis(5));
+        assert_eq!(controller.mode(), None);
+        assert!(!controller.is_idle());
+        assert_ne!(frame.get(), FRAME_OFF);
+
+        run(&mut controller, &clock, config.onoff_fade_duration);
+        assert!(controller.is_idle());
+        assert_eq!(frame.get(), FRAME_OFF);
+    }
+
+    #[test]
+    fn auto_on_doesnt_replace_a_selected_color() {
+        let frame = Cell::new(FRAME_OFF);
+        let clock = MockClock::new();
+        let config = ControllerConfig::default();
+        let mut controller = Controller::new(RecordingDriver(&frame), &clock, config).unwrap();
+
+        controller.handle_ir_command(IrCommand {
+            button: Button::Blue,
+            repeat: false,
+        });
+        run(&mut controller, &clock, config.onoff_fade_duration);
+        assert_eq!(frame.get()[0], BLUE);
+
+        controller.handle_auto_on_event();
+        run(&mut controller, &clock, config.onoff_fade_duration);
+        assert_eq!(controller.mode(), Some(Mode::ManualOn));
+        assert_eq!(frame.get()[0], BLUE);
+    }
+
+    #[test]
+    fn auto_on_extends_auto_on() {
+        let frame = Cell::new(FRAME_OFF);
+        let clock = MockClock::new();
+        let config = ControllerConfig::default();
+        let mut controller = Controller::new(RecordingDriver(&frame), &clock, config).unwrap();
+
+        let almost = Duration::from_millis(config.auto_on_duration.as_millis() - 1000);
+        controller.handle_auto_on_event();
+        run(&mut controller, &clock, almost);
+        controller.handle_auto_on_event();
+        run(&mut controller, &clock, almost);
+        assert_eq!(controller.mode(), Some(Mode::AutoOn));
+        assert_eq!(frame.get()[0], DEFAULT_COLOR);
+    }
+
+    #[test]
+    fn auto_on_follows_the_schedule() {
+        let frame = Cell::new(FRAME_OFF);
+        let clock = MockClock::new();
+        let mut config = ControllerConfig::default();
+        let light = AutoOnLight {
+            color: BLUE,
+            duration: Duration::ONE_MINUTE,
+        };
+        config.schedule.set_window(
+            0,
+            Some(ScheduleWindow {
+                start: TimeOfDay::new(20, 0, 0).unwrap(),
+                end: TimeOfDay::new(7, 0, 0).unwrap(),
+                action: AutoOnAction::Light(light),
+            }),
+        );
+        let mut controller = Controller::new(RecordingDriver(&frame), &clock, config).unwrap();
+
+        // Unknown time, the defaults
+        controller.handle_auto_on_event();
+        run(&mut controller, &clock, config.onoff_fade_duration);
+        assert_eq!(controller.mode(), Some(Mode::AutoOn));
+        assert_eq!(frame.get()[0], DEFAULT_COLOR);
+        run(&mut controller, &clock, config.auto_on_duration);
+        assert!(controller.is_idle());
+
+        controller.handle_time_of_day(TimeOfDay::new(12, 0, 0).unwrap());
+        controller.handle_auto_on_event();
+        run(&mut controller, &clock, config.onoff_fade_duration);
+        assert_eq!(controller.mode(), None);
+        assert_eq!(frame.get(), FRAME_OFF);
+
+        controller.handle_time_of_day(TimeOfDay::new(23, 0, 0).unwrap());
+        controller.handle_auto_on_event();
+        run(&mut controller, &clock, config.onoff_fade_duration);
+        assert_eq!(controller.mode(), Some(Mode::AutoOn));
+        assert_eq!(frame.get()[0], BLUE);
+        run(&mut controller, &clock, light.duration);
+        assert!(controller.is_idle());
+    }
+
+    #[test]
+    fn strobe_cuts_fully_on_and_off() {
+        let frame = Cell::new(FRAME_OFF);
+        let clock = MockClock::new();
+        let config = ControllerConfig {
+            strobe_mode_frequency_hz: 4,
+            strobe_mode_duty_percent: 25,
+            ..Default::default()
+        };
+        let mut controller = Controller::new(RecordingDriver(&frame), &clock, config).unwrap();
+
+        press(&mut controller, Button::Blue);
+        run(&mut controller, &clock, config.onoff_fade_duration);
+        press(&mut controller, Button::Strobe);
+        assert_eq!(frame.get(), [Strobe::COLOR; MAX_NUM_LEDS]);
+
+        // 250 ms period, 62 ms on
+        for step in 1..=100 {
+            run(&mut controller, &clock, Duration::from_millis(5));
+            let expected = if (step * 5) % 250 < 62 {
+                Strobe::COLOR
+            } else {
+                COLOR_OFF
+            };
+            assert_eq!(frame.get(), [expected; MAX_NUM_LEDS], "{} ms", step * 5);
+        }
+        assert_eq!(controller.mode(), Some(Mode::Effect(EffectId::Strobe)));
+    }
+
+    #[test]
+    fn flash_holds_each_color_then_jumps() {
+        let frame = Cell::new(FRAME_OFF);
+        let clock = MockClock::new();
+        let config = ControllerConfig {
+            flash_mode_hold_duration: Duration::from_millis(500),
+            ..Default::default()
+        };
+        let mut controller = Controller::new(RecordingDriver(&frame), &clock, config).unwrap();
+        let is_basic = |f: Frame| {
+            BasicColor::enumerate()
+                .iter()
+                .any(|c| f == [c.as_rgbw(); MAX_NUM_LEDS])
+        };
+
+        // No fade in from off either
+        press(&mut controller, Button::Flash);
+        let mut color = frame.get();
+        assert!(is_basic(color));
+
+        for _ in 0..4 {
+            run(&mut controller, &clock, Duration::from_millis(495));
+            assert_eq!(frame.get(), color);
+            run(&mut controller, &clock, Duration::from_millis(5));
+            assert_ne!(frame.get(), color);
+            assert!(is_basic(frame.get()));
+            color = frame.get();
+        }
+        assert_eq!(controller.mode(), Some(Mode::Effect(EffectId::Flash)));
+    }
+
+    #[test]
+    fn learned_remote_is_allowed() {
+        let frame = Cell::new(FRAME_OFF);
+        let clock = MockClock::new();
+        let mut controller =
+            Controller::new(RecordingDriver(&frame), &clock, ControllerConfig::default()).unwrap();
+        let remote = IrAddress {
+            protocol: IrProtocol::Rc5,
+            address: 20,
+        };
+        let key = |command| IrKey {
+            protocol: remote.protocol,
+            address: remote.address,
+            command,
+        };
+        assert!(!controller.ir_filter().accepts(&key(0)));
+
+        controller.start_ir_learning();
+        for command in 0..NUM_BUTTONS as u8 {
+            assert!(controller.ir_filter().accepts(&key(command)));
+            controller.handle_ir_frame(IrFrame {
+                key: key(command),
+                repeat: false,
+            });
+        }
+        assert!(!controller.is_ir_learning());
+
+        let filter = controller.ir_filter();
+        assert!(!filter.is_any());
+        assert!(filter.contains(remote));
+        assert!(filter.contains(STOCK_REMOTE));
+        assert!(!filter.accepts(&IrKey {
+            protocol: IrProtocol::Rc5,
+            address: 21,
+            command: 0,
+        }));
+    }
+
+    #[test]
+    fn console_controls_the_light() {
+        let frame = Cell::new(FRAME_OFF);
+        let clock = MockClock::new();
+        let config = ControllerConfig::default();
+        let mut controller = Controller::new(RecordingDriver(&frame), &clock, config).unwrap();
+
+        assert_eq!(
+            controller.handle_console_command(ConsoleCommand::On),
+            Ok(())
+        );
+        run(&mut controller, &clock, config.onoff_fade_duration);
+        assert_eq!(controller.mode(), Some(Mode::ManualOn));
+        assert_eq!(frame.get()[0], DEFAULT_COLOR);
+
+        assert_eq!(
+            controller.handle_console_command(ConsoleCommand::Color(BLUE)),
+            Ok(())
+        );
+        run(&mut controller, &clock, config.onoff_fade_duration);
+        assert_eq!(frame.get()[0], BLUE);
+
+        let candle = Mode::Effect(EffectId::Candle);
+        assert_eq!(
+            controller.handle_console_command(ConsoleCommand::Mode(candle)),
+            Ok(())
+        );
+        assert_eq!(controller.mode(), Some(candle));
+        assert_eq!(
+            controller.handle_console_command(ConsoleCommand::Mode(Mode::Sleep)),
+            Ok(())
+        );
+        assert_eq!(controller.mode(), Some(Mode::Sleep));
+
+        assert_eq!(
+            controller.handle_console_command(ConsoleCommand::Off),
+            Ok(())
+        );
+        assert_eq!(controller.mode(), None);
+        run(&mut controller, &clock, config.onoff_fade_duration);
+        assert!(controller.is_idle());
+    }
+
+    #[test]
+    fn console_rejects_modes_that_cant_be_selected() {
+        let frame = Cell::new(FRAME_OFF);
+        let clock = MockClock::new();
+        let config = ControllerConfig::default();
+        let mut controller = Controller::new(RecordingDriver(&frame), &clock, config).unwrap();
+
+        for mode in [Mode::AutoOn, Mode::ManualOn, Mode::Sunrise] {
+            assert_eq!(
+                controller.handle_console_command(ConsoleCommand::Mode(mode)),
+                Err(ConsoleError::InvalidArgument),
+                "{:?}",
+                mode
+            );
+            assert_eq!(controller.mode(), None);
+        }
+    }
+
+    #[test]
+    fn console_edits_the_schedule_and_remotes() {
+        let frame = Cell::new(FRAME_OFF);
+        let clock = MockClock::new();
+        let config = ControllerConfig::default();
+        let mut controller = Controller::new(RecordingDriver(&frame), &clock, config).unwrap();
+        let window = |duration| ScheduleWindow {
+            start: TimeOfDay::new(20, 0, 0).unwrap(),
+            end: TimeOfDay::new(7, 0, 0).unwrap(),
+            action: AutoOnAction::Light(AutoOnLight {
+                color: BLUE,
+                duration,
+            }),
+        };
+
+        let cmd = ConsoleCommand::Schedule(1, Some(window(Duration::ONE_MINUTE)));
+        assert_eq!(controller.handle_console_command(cmd), Ok(()));
+        assert_eq!(
+            controller.config().schedule.window(1),
+            Some(window(Duration::ONE_MINUTE))
+        );
+        // Over before the fade in finishes
+        let cmd = ConsoleCommand::Schedule(1, Some(window(Duration::ZERO)));
+        assert_eq!(
+            controller.handle_console_command(cmd),
+            Err(ConsoleError::InvalidArgument)
+        );
+        assert_eq!(
+            controller.config().schedule.window(1),
+            Some(window(Duration::ONE_MINUTE))
+        );
+
+        let remote = IrAddress {
+            protocol: IrProtocol::Rc5,
+            address: 20,
+        };
+        controller
+            .handle_console_command(ConsoleCommand::IrAllow(remote))
+            .unwrap();
+        controller
+            .handle_console_command(ConsoleCommand::IrDeny(STOCK_REMOTE))
+            .unwrap();
+        assert!(controller.ir_filter().addresses().eq([remote].iter()));
+        controller
+            .handle_console_command(ConsoleCommand::IrAny(true))
+            .unwrap();
+        assert!(controller.ir_filter().is_any());
+    }
+
+    #[test]
+    fn mode_button_steps_the_speed() {
+        let frame = Cell::new(FRAME_OFF);
+        let clock = MockClock::new();
+        let config = ControllerConfig::default();
+        let mut controller = Controller::new(RecordingDriver(&frame), &clock, config).unwrap();
+
+        press(&mut controller, Button::Strobe);
+        assert_eq!(controller.mode(), Some(Mode::Effect(EffectId::Strobe)));
+        assert_eq!(
+            controller.speed(Mode::Effect(EffectId::Strobe)),
+            Some(Speed::Normal)
+        );
+
+        // Brightness stays on the brightness buttons
+        press(&mut controller, Button::BrightnessDown);
+        assert_eq!(controller.brightness(), BRIGHTNESS_MAX - BRIGHTNESS_STEP);
+        press(&mut controller, Button::BrightnessUp);
+        assert_eq!(controller.brightness(), BRIGHTNESS_MAX);
+        assert_eq!(
+            controller.speed(Mode::Effect(EffectId::Strobe)),
+            Some(Speed::Normal)
+        );
+
+        press(&mut controller, Button::Strobe);
+        assert_eq!(
+            controller.speed(Mode::Effect(EffectId::Strobe)),
+            Some(Speed::Faster)
+        );
+        press(&mut controller, Button::Strobe);
+        press(&mut controller, Button::Strobe);
+        assert_eq!(
+            controller.speed(Mode::Effect(EffectId::Strobe)),
+            Some(Speed::Slowest)
+        );
+        assert_eq!(controller.mode(), Some(Mode::Effect(EffectId::Strobe)));
+
+        // Each mode keeps its own
+        press(&mut controller, Button::Fade);
+        assert_eq!(controller.mode(), Some(Mode::Effect(EffectId::Fade)));
+        press(&mut controller, Button::Fade);
+        assert_eq!(
+            controller.speed(Mode::Effect(EffectId::Fade)),
+            Some(Speed::Faster)
+        );
+        assert_eq!(
+            controller.speed(Mode::Effect(EffectId::Strobe)),
+            Some(Speed::Slowest)
+        );
+        assert_eq!(controller.speed(Mode::ManualOn), None);
+
+        let settings = controller.settings();
+        let mut controller = Controller::new(RecordingDriver(&frame), &clock, config).unwrap();
+        controller.restore_settings(&settings).unwrap();
+        assert_eq!(
+            controller.speed(Mode::Effect(EffectId::Fade)),
+            Some(Speed::Faster)
+        );
+        assert_eq!(
+            controller.speed(Mode::Effect(EffectId::Strobe)),
+            Some(Speed::Slowest)
+        );
+    }
+
+    #[test]
+    fn fastest_strobe_keeps_both_phases_an_update_long() {
+        let frame = Cell::new(FRAME_OFF);
+        let clock = MockClock::new();
+        let config = ControllerConfig {
+            strobe_mode_frequency_hz: 25,
+            strobe_mode_duty_percent: 13,
+            ..Default::default()
+        };
+        let mut controller = Controller::new(RecordingDriver(&frame), &clock, config).unwrap();
+
+        press(&mut controller, Button::Strobe);
+        controller.set_speed(Mode::Effect(EffectId::Strobe), Speed::Fastest);
+        // 10 ms period, the 1.3 ms on time would mostly fall between updates
+        for step in 1..=20 {
+            run(&mut controller, &clock, Duration::from_millis(5));
+            let expected = if step % 2 == 0 {
+                Strobe::COLOR
+            } else {
+                COLOR_OFF
+            };
+            assert_eq!(frame.get(), [expected; MAX_NUM_LEDS], "{} ms", step * 5);
+        }
+    }
+
+    #[test]
+    fn speed_scales_the_fade_time() {
+        let frame = Cell::new(FRAME_OFF);
+        let clock = MockClock::new();
+        let config = ControllerConfig {
+            smooth_mode_fade_duration: Duration::from_millis(4000),
+            ..Default::default()
+        };
+        let mut controller = Controller::new(RecordingDriver(&frame), &clock, config).unwrap();
+        let is_basic = |f: Frame| {
+            BasicColor::enumerate()
+                .iter()
+                .any(|c| f == [c.as_rgbw(); MAX_NUM_LEDS])
+        };
+
+        // Smooth fades between basic colors, a basic color is only shown once a fade is done
+        controller.set_speed(Mode::Effect(EffectId::Smooth), Speed::Fastest);
+        press(&mut controller, Button::Smooth);
+        run(&mut controller, &clock, Duration::from_millis(995));
+        assert!(!is_basic(frame.get()));
+        run(&mut controller, &clock, Duration::from_millis(5));
+        assert!(is_basic(frame.get()));
+
+        // Slowing down midway keeps the progress, 250 of 1000 ms is 1000 of 4000 ms
+        run(&mut controller, &clock, Duration::from_millis(250));
+        let before = frame.get();
+        controller.set_speed(Mode::Effect(EffectId::Smooth), Speed::Normal);
+        run(&mut controller, &clock, Duration::from_millis(5));
+        let diff = |a: u8, b: u8| (a as i16 - b as i16).abs();
+        assert!(diff(frame.get()[0].r, before[0].r) <= 2);
+        assert!(diff(frame.get()[0].g, before[0].g) <= 2);
+        assert!(diff(frame.get()[0].b, before[0].b) <= 2);
+        run(&mut controller, &clock, Duration::from_millis(2990));
+        assert!(!is_basic(frame.get()));
+        run(&mut controller, &clock, Duration::from_millis(5));
+        assert!(is_basic(frame.get()));
+    }
+
+    #[test]
+    fn settings_are_saved_and_restored() {
+        let frame = Cell::new(FRAME_OFF);
+        let clock = MockClock::new();
+        let config = ControllerConfig::default();
+        let mut controller = Controller::new(RecordingDriver(&frame), &clock, config).unwrap();
+
+        for button in [Button::Blue, Button::BrightnessDown, Button::BrightnessDown].iter() {
+            controller.handle_ir_command(IrCommand {
+                button: *button,
+                repeat: false,
+            });
+        }
+        let settings = controller.settings();
+        assert_eq!(settings.mode, Mode::ManualOn);
+        assert_eq!(settings.color, BLUE);
+        assert_eq!(settings.brightness, BRIGHTNESS_MAX - (2 * BRIGHTNESS_STEP));
+
+        let mut controller = Controller::new(RecordingDriver(&frame), &clock, config).unwrap();
+        controller.restore_settings(&settings).unwrap();
+        assert_eq!(controller.settings(), settings);
+        assert!(controller.is_idle());
+    }
+
+    #[test]
+    fn restored_brightness_is_at_least_the_min() {
+        let frame = Cell::new(FRAME_OFF);
+        let clock = MockClock::new();
+        let config = ControllerConfig::default();
+        let mut controller = Controller::new(RecordingDriver(&frame), &clock, config).unwrap();
+
+        let mut settings = controller.settings();
+        settings.brightness = 0;
+        controller.restore_settings(&settings).unwrap();
+        assert_eq!(controller.brightness(), BRIGHTNESS_MIN);
+    }
+
+    #[test]
+    fn holding_on_starts_the_sleep_timer() {
+        let frame = Cell::new(FRAME_OFF);
+        let clock = MockClock::new();
+        let config = ControllerConfig::default();
+        let mut controller = Controller::new(RecordingDriver(&frame), &clock, config).unwrap();
+
+        press(&mut controller, Button::Blue);
+        run(&mut controller, &clock, config.onoff_fade_duration);
+        press(&mut controller, Button::On);
+        assert_eq!(controller.mode(), Some(Mode::ManualOn));
+        assert_eq!(controller.sleep_remaining(), None);
+
+        hold_on(&mut controller);
+        assert_eq!(controller.mode(), Some(Mode::Sleep));
+        assert_eq!(
+            controller.sleep_remaining(),
+            Some(config.sleep_timer.duration())
+        );
+
+        // A press in Mode::Sleep doesn't cancel it
+        press(&mut controller, Button::On);
+        assert_eq!(controller.mode(), Some(Mode::Sleep));
+    }
+
+    #[test]
+    fn remote_on_starts_the_sleep_timer_once_held() {
+        let frame = Cell::new(FRAME_OFF);
+        let clock = MockClock::new();
+        let config = ControllerConfig::default();
+        let mut controller = Controller::new(RecordingDriver(&frame), &clock, config).unwrap();
+
+        send(&mut controller, &clock, Button::On, 0);
+        assert_eq!(controller.mode(), Some(Mode::ManualOn));
+        // Two repeat frames are 216 ms, short of IR_HOLD_DURATION
+        send(&mut controller, &clock, Button::On, 2);
+        assert_eq!(controller.mode(), Some(Mode::ManualOn));
+        send(&mut controller, &clock, Button::On, 3);
+        assert_eq!(controller.mode(), Some(Mode::Sleep));
+        assert_eq!(controller.config().sleep_timer, SleepTimer::ThirtyMinutes);
+
+        // Held for longer, the timer only steps once
+        send(&mut controller, &clock, Button::On, 10);
+        assert_eq!(controller.config().sleep_timer, SleepTimer::SixtyMinutes);
+        assert_eq!(controller.mode(), Some(Mode::Sleep));
+    }
+
+    #[test]
+    fn holding_brightness_keeps_stepping() {
+        let frame = Cell::new(FRAME_OFF);
+        let clock = MockClock::new();
+        let config = ControllerConfig::default();
+        let mut controller = Controller::new(RecordingDriver(&frame), &clock, config).unwrap();
+
+        send(&mut controller, &clock, Button::BrightnessDown, 0);
+        assert_eq!(controller.brightness(), BRIGHTNESS_MAX - BRIGHTNESS_STEP);
+        // The press, then the hold at the 3rd repeat frame and a step on each one after
+        send(&mut controller, &clock, Button::BrightnessDown, 5);
+        assert_eq!(
+            controller.brightness(),
+            BRIGHTNESS_MAX - 5 * BRIGHTNESS_STEP
+        );
+
+        // Other buttons only act on the press
+        send(&mut controller, &clock, Button::Flash, 10);
+        assert_eq!(controller.mode(), Some(Mode::Effect(EffectId::Flash)));
+        assert_eq!(
+            controller.speed(Mode::Effect(EffectId::Flash)),
+            Some(Speed::Normal)
+        );
+    }
+
+    #[test]
+    fn holding_on_while_off_doesnt_start_the_sleep_timer() {
+        let frame = Cell::new(FRAME_OFF);
+        let clock = MockClock::new();
+        let config = ControllerConfig::default();
+        let mut controller = Controller::new(RecordingDriver(&frame), &clock, config).unwrap();
+
+        hold_on(&mut controller);
+        assert_eq!(controller.mode(), Some(Mode::ManualOn));
+    }
+
+    #[test]
+    fn holding_on_again_cycles_the_sleep_timer() {
+        let frame = Cell::new(FRAME_OFF);
+        let clock = MockClock::new();
+        let config = ControllerConfig::default();
+        let mut controller = Controller::new(RecordingDriver(&frame), &clock, config).unwrap();
+        assert_eq!(config.sleep_timer, SleepTimer::ThirtyMinutes);
+
+        press(&mut controller, Button::White);
+        hold_on(&mut controller);
+        run(&mut controller, &clock, Duration::ONE_MINUTE);
+
+        hold_on(&mut controller);
+        assert_eq!(controller.config().sleep_timer, SleepTimer::SixtyMinutes);
+        assert_eq!(controller.sleep_remaining(), Some(Duration::ONE_HOUR));
+
+        hold_on(&mut controller);
+        assert_eq!(controller.config().sleep_timer, SleepTimer::FifteenMinutes);
+        assert_eq!(
+            controller.sleep_remaining(),
+            Some(SleepTimer::FifteenMinutes.duration())
+        );
+        assert_eq!(controller.mode(), Some(Mode::Sleep));
+    }
+
+    #[test]
+    fn sleep_dims_down_then_turns_off() {
+        let frame = Cell::new(FRAME_OFF);
+        let clock = MockClock::new();
+        let config = ControllerConfig::default();
+        let mut controller = Controller::new(RecordingDriver(&frame), &clock, config).unwrap();
+
+        press(&mut controller, Button::White);
+        run(&mut controller, &clock, config.onoff_fade_duration);
+        hold_on(&mut controller);
+
+        // Every channel only goes down, the red and green of SLEEP_COLOR are capped at
+        // the white they start from
+        let mut last = frame.get()[0];
+        let sleep_ms = config.sleep_timer.duration().as_millis();
+        for _ in 0..(sleep_ms / 5) - 1 {
+            run(&mut controller, &clock, Duration::from_millis(5));
+            let color = frame.get()[0];
+            assert!(color.r <= last.r && color.g <= last.g && color.b <= last.b);
+            assert!(color.a.0 <= last.a.0);
+            last = color;
+        }
+        assert_eq!(controller.mode(), Some(Mode::Sleep));
+
+        run(&mut controller, &clock, Duration::from_millis(5));
+        assert_eq!(controller.mode(), None);
+        assert_eq!(frame.get()[0], COLOR_OFF);
+        run(&mut controller, &clock, config.onoff_fade_duration);
+        assert!(controller.is_idle());
+    }
+
+    #[test]
+    fn sleep_dims_to_amber() {
+        let frame = Cell::new(FRAME_OFF);
+        let clock = MockClock::new();
+        let config = ControllerConfig::default();
+        let mut controller = Controller::new(RecordingDriver(&frame), &clock, config).unwrap();
+
+        press(&mut controller, Button::Red);
+        run(&mut controller, &clock, config.onoff_fade_duration);
+        hold_on(&mut controller);
+        run(&mut controller, &clock, config.sleep_timer.duration());
+        // The amber's green is capped at the red's
+        assert_eq!(
+            frame.get()[0],
+            RGBW8::new_alpha(SLEEP_COLOR.r, 0, 0, White(0))
+        );
+    }
+
+    #[test]
+    fn long_press_cycles_through_the_effects() {
+        let frame = Cell::new(FRAME_OFF);
+        let clock = MockClock::new();
+        let config = ControllerConfig::default();
+        let mut controller = Controller::new(RecordingDriver(&frame), &clock, config).unwrap();
+
+        for id in EffectId::enumerate() {
+            controller.handle_button_gesture(Gesture::LongPress);
+            assert_eq!(controller.mode(), Some(Mode::Effect(*id)));
+        }
+        controller.handle_button_gesture(Gesture::LongPress);
+        assert_eq!(controller.mode(), Some(Mode::Effect(EffectId::Fade)));
+    }
+
+    #[test]
+    fn effects_render_every_pixel() {
+        let frame = Cell::new(FRAME_OFF);
+        let clock = MockClock::new();
+        let config = ControllerConfig::default();
+        let mut controller = Controller::new(RecordingDriver(&frame), &clock, config).unwrap();
+
+        controller.run_effect(EffectId::TheaterChase);
+        run(&mut controller, &clock, config.onoff_fade_duration);
+        let lit = |frame: &Frame| {
+            let mut lit = [false; MAX_NUM_LEDS];
+            lit.iter_mut()
+                .zip(frame.iter())
+                .for_each(|(l, p)| *l = *p != COLOR_OFF);
+            lit
+        };
+        let first = lit(&frame.get());
+        assert!(first.iter().any(|l| *l));
+        assert!(first.iter().any(|l| !*l));
+
+        run(&mut controller, &clock, TheaterChase::STEP);
+        let second = lit(&frame.get());
+        for i in 0..MAX_NUM_LEDS {
+            assert_eq!(second[(i + 1) % MAX_NUM_LEDS], first[i], "{}", i);
+        }
+    }
+
+    #[test]
+    fn ir_learning_replaces_the_keymap() {
+        let frame = Cell::new(FRAME_OFF);
+        let clock = MockClock::new();
+        let config = ControllerConfig::default();
+        let mut controller = Controller::new(RecordingDriver(&frame), &clock, config).unwrap();
+        let key = |command| IrFrame {
+            key: IrKey {
+                protocol: IrProtocol::Nec16,
+                address: 0x1234,
+                command,
+            },
+            repeat: false,
+        };
+
+        controller.start_ir_learning();
+        assert_eq!(controller.mode(), Some(Mode::IrLearning));
+        for (i, _) in Button::enumerate().iter().enumerate() {
+            assert!(controller.is_ir_learning());
+            // The stock remote's keys are learned like any other while learning
+            controller.handle_ir_frame(key(i as u8));
+            run(&mut controller, &clock, Duration::from_millis(5));
+        }
+        assert!(!controller.is_ir_learning());
+        assert_eq!(controller.mode(), None);
+
+        // 3 is now On, the stock On is an unknown key
+        for (command, mode) in [
+            (3, Some(Mode::ManualOn)),
+            (7, Some(Mode::ManualOn)),
+            (2, None),
+        ] {
+            controller.handle_ir_frame(key(command));
+            run(&mut controller, &clock, IR_RELEASE_TIMEOUT);
+            assert_eq!(controller.mode(), mode);
+        }
+    }
+}
@@ -0,0 +1,128 @@
+use crate::{Duration, Instant};
+
+/// Time based debouncer for a sampled digital level.
+/// A new level is only accepted once it has been stable for the debounce duration.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct Debouncer {
+    duration: Duration,
+    level: bool,
+    candidate: bool,
+    candidate_since: Instant,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub enum Edge {
+    Rising,
+    Falling,
+}
+
+impl Debouncer {
+    pub const fn new(duration: Duration, initial_level: bool, now: Instant) -> Self {
+        Debouncer {
+            duration,
+            level: initial_level,
+            candidate: initial_level,
+            candidate_since: now,
+        }
+    }
+
+    /// The debounced level
+    pub fn level(&self) -> bool {
+        self.level
+    }
+
+    /// Feed a raw sample, returns the edge when the debounced level changes
+    pub fn update(&mut self, sample: bool, now: Instant) -> Option<Edge> {
+        if sample != self.candidate {
+            self.candidate = sample;
+            self.candidate_since = now;
+        }
+
+        if self.candidate != self.level && now.duration_since(self.candidate_since) >= self.duration
+        {
+            self.level = self.candidate;
+            Some(if self.level {
+                Edge::Rising
+            } else {
+                Edge::Falling
+            })
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::VIBRATION_DEBOUNCE_DURATION;
+
+    /// Feeds one sample per millisecond from `start`, returns the edges and when they came
+    fn script(debouncer: &mut Debouncer, start: u32, samples: &[bool]) -> [Option<(u32, Edge)>; 4] {
+        let mut edges = [None; 4];
+        let mut count = 0;
+        for (i, sample) in samples.iter().enumerate() {
+            let now = start.wrapping_add(i as u32);
+            if let Some(edge) = debouncer.update(*sample, Instant::from_millis(now)) {
+                edges[count] = Some((now, edge));
+                count += 1;
+            }
+        }
+        edges
+    }
+
+    #[test]
+    fn glitches_are_ignored() {
+        let glitch = VIBRATION_DEBOUNCE_DURATION.as_millis() as usize - 1;
+        let mut samples = [false; 64];
+        samples[10..10 + glitch].iter_mut().for_each(|s| *s = true);
+        samples[30..31].iter_mut().for_each(|s| *s = true);
+
+        let mut debouncer = Debouncer::new(VIBRATION_DEBOUNCE_DURATION, false, Instant::ZERO);
+        assert_eq!(script(&mut debouncer, 0, &samples), [None; 4]);
+        assert!(!debouncer.level());
+    }
+
+    #[test]
+    fn stable_levels_are_accepted_after_the_duration() {
+        let duration = VIBRATION_DEBOUNCE_DURATION.as_millis();
+        let mut samples = [false; 64];
+        samples[10..40].iter_mut().for_each(|s| *s = true);
+        // A glitch low while high doesn't count as a release
+        samples[20] = false;
+
+        let mut debouncer = Debouncer::new(VIBRATION_DEBOUNCE_DURATION, false, Instant::ZERO);
+        assert_eq!(
+            script(&mut debouncer, 0, &samples),
+            [
+                Some((21 + duration, Edge::Rising)),
+                Some((40 + duration, Edge::Falling)),
+                None,
+                None
+            ]
+        );
+    }
+
+    #[test]
+    fn clock_wrap_around() {
+        let mut samples = [false; 32];
+        samples[4..].iter_mut().for_each(|s| *s = true);
+        let start = u32::MAX - 8;
+
+        let mut debouncer = Debouncer::new(
+            VIBRATION_DEBOUNCE_DURATION,
+            false,
+            Instant::from_millis(start),
+        );
+        let edges = script(&mut debouncer, start, &samples);
+        assert_eq!(
+            edges[0],
+            Some((
+                start
+                    .wrapping_add(4)
+                    .wrapping_add(VIBRATION_DEBOUNCE_DURATION.as_millis()),
+                Edge::Rising
+            ))
+        );
+    }
+}
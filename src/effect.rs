@@ -0,0 +1,293 @@
+use crate::{
+    Button, ControllerConfig, Duration, FadeToRgbw, Instant, Interpolation, RandomColorGen, Speed,
+    RGBW8,
+};
+
+mod candle;
+mod color_wipe;
+mod comet;
+mod fade;
+mod flash;
+mod rainbow_chase;
+mod smooth;
+mod strobe;
+mod theater_chase;
+
+pub use candle::*;
+pub use color_wipe::*;
+pub use comet::*;
+pub use fade::*;
+pub use flash::*;
+pub use rainbow_chase::*;
+pub use smooth::*;
+pub use strobe::*;
+pub use theater_chase::*;
+
+/// Sub-pixel resolution of moving effects
+const SUBPIXELS: u32 = 256;
+
+/// What an effect gets to work with on each call
+pub struct EffectContext<'a> {
+    pub now: Instant,
+    pub config: &'a ControllerConfig,
+    /// The effect's speed, see `EffectClock`
+    pub speed: Speed,
+    pub rng: &'a mut RandomColorGen,
+}
+
+/// An animation the controller runs in `Mode::Effect`.
+///
+/// Adding one takes three edits, all in this module:
+/// its own file under `effect/` implementing this and `Default`,
+/// the `mod` and `pub use` lines for that file above,
+/// and its line at the end of the `effects!` registry below.
+/// The state machine, the long press cycle and the persisted speeds pick it up from there
+pub trait Effect {
+    /// Starts the effect, `from` is the color showing when it was selected.
+    /// The frame is rendered right after
+    fn init(&mut self, ctx: &mut EffectContext, from: RGBW8);
+
+    /// Called on every controller update, returns true if the frame needs rendering.
+    /// The frame is only written out if it changed
+    fn tick(&mut self, ctx: &mut EffectContext) -> bool;
+
+    /// Pixel positions come from their index within the frame, so any ring size works
+    fn render(&self, frame: &mut [RGBW8]);
+}
+
+/// Effect time, runs slower or faster than the clock with the effect's speed.
+/// Changing the speed doesn't make the animation jump
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct EffectClock {
+    last_tick: Instant,
+    /// Quarter milliseconds, Speed::Slowest runs at a quarter of the clock rate
+    elapsed: u64,
+}
+
+impl EffectClock {
+    pub fn start(&mut self, now: Instant) {
+        self.last_tick = now;
+        self.elapsed = 0;
+    }
+
+    /// Advances to `now`, returns the effect time since the start
+    pub fn tick(&mut self, now: Instant, speed: Speed) -> Duration {
+        self.elapsed += now.duration_since(self.last_tick).as_millis() as u64 * speed.rate() as u64;
+        self.last_tick = now;
+        self.elapsed()
+    }
+
+    /// Wraps around like `Instant`
+    pub fn elapsed(&self) -> Duration {
+        Duration::from_millis((self.elapsed / 4) as u32)
+    }
+}
+
+/// Fades from one color into another, timed in effect time
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct Crossfade {
+    pub color: RGBW8,
+    pub start_color: RGBW8,
+    pub destination_color: RGBW8,
+    pub started_at: Duration,
+}
+
+impl Crossfade {
+    pub fn start(&mut self, color: RGBW8, destination_color: RGBW8, now: Duration) {
+        self.color = color;
+        self.fade_to(destination_color, now);
+    }
+
+    /// Restart the fade from the current color
+    pub fn fade_to(&mut self, destination_color: RGBW8, now: Duration) {
+        self.start_color = self.color;
+        self.destination_color = destination_color;
+        self.started_at = now;
+    }
+
+    /// Returns true if the color changed
+    pub fn update(
+        &mut self,
+        now: Duration,
+        duration: Duration,
+        interpolation: Interpolation,
+    ) -> bool {
+        let prev_color = self.color;
+        let elapsed = now.duration_since(self.started_at);
+        self.color = match interpolation {
+            Interpolation::Rgb => {
+                self.start_color
+                    .interpolate(&self.destination_color, elapsed, duration)
+            }
+            Interpolation::Hsv => {
+                self.start_color
+                    .interpolate_hsv(&self.destination_color, elapsed, duration)
+            }
+        };
+        self.color != prev_color
+    }
+
+    pub fn destination_color_reached(&self) -> bool {
+        self.color.destination_reached(&self.destination_color)
+    }
+}
+
+/// Render a single color to every pixel
+pub fn fill(frame: &mut [RGBW8], color: &RGBW8) {
+    frame.iter_mut().for_each(|p| *p = *color);
+}
+
+macro_rules! effects {
+    ($($id:ident => $effect:ty,)*) => {
+        /// The registered effects. Ids are persisted in the settings,
+        /// new effects go at the end
+        #[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+        pub enum EffectId {
+            $($id,)*
+        }
+
+        impl EffectId {
+            pub const COUNT: usize = [$(stringify!($id),)*].len();
+
+            pub fn enumerate() -> &'static [Self] {
+                &[$(EffectId::$id,)*]
+            }
+
+            pub fn from_u8(value: u8) -> Option<Self> {
+                Self::enumerate().get(value as usize).copied()
+            }
+
+            pub fn name(self) -> &'static str {
+                match self {
+                    $(EffectId::$id => stringify!($id),)*
+                }
+            }
+
+            /// The effect, not started yet
+            pub fn effect(self) -> EffectState {
+                match self {
+                    $(EffectId::$id => EffectState::$id(<$effect>::default()),)*
+                }
+            }
+        }
+
+        /// One of the registered effects
+        #[derive(Copy, Clone, PartialEq, Debug)]
+        pub enum EffectState {
+            $($id($effect),)*
+        }
+
+        impl EffectState {
+            pub fn id(&self) -> EffectId {
+                match self {
+                    $(EffectState::$id(_) => EffectId::$id,)*
+                }
+            }
+        }
+
+        impl Effect for EffectState {
+            fn init(&mut self, ctx: &mut EffectContext, from: RGBW8) {
+                match self {
+                    $(EffectState::$id(e) => e.init(ctx, from),)*
+                }
+            }
+
+            fn tick(&mut self, ctx: &mut EffectContext) -> bool {
+                match self {
+                    $(EffectState::$id(e) => e.tick(ctx),)*
+                }
+            }
+
+            fn render(&self, frame: &mut [RGBW8]) {
+                match self {
+                    $(EffectState::$id(e) => e.render(frame),)*
+                }
+            }
+        }
+    };
+}
+
+effects! {
+    Fade => Fade,
+    Strobe => Strobe,
+    Smooth => Smooth,
+    Flash => Flash,
+    RainbowChase => RainbowChase,
+    Comet => Comet,
+    TheaterChase => TheaterChase,
+    ColorWipe => ColorWipe,
+    Candle => Candle,
+}
+
+impl EffectId {
+    pub fn from_button(b: Button) -> Option<Self> {
+        Some(match b {
+            Button::Fade => EffectId::Fade,
+            Button::Strobe => EffectId::Strobe,
+            Button::Smooth => EffectId::Smooth,
+            Button::Flash => EffectId::Flash,
+            _ => return None,
+        })
+    }
+}
+
+impl From<EffectId> for u8 {
+    fn from(id: EffectId) -> Self {
+        id as u8
+    }
+}
+
+/// Starts an effect and renders it into a 12 pixel ring, `elapsed` later
+#[cfg(test)]
+fn render_at<E: Effect + Default>(seed: u64, speed: Speed, elapsed: Duration) -> [RGBW8; 12] {
+    let config = ControllerConfig::default();
+    let mut rng = RandomColorGen::new(seed);
+    let mut ctx = EffectContext {
+        now: Instant::from_millis(0),
+        config: &config,
+        speed,
+        rng: &mut rng,
+    };
+    let mut effect = E::default();
+    effect.init(&mut ctx, RGBW8::default());
+    ctx.now = ctx.now + elapsed;
+    effect.tick(&mut ctx);
+    let mut frame = [RGBW8::default(); 12];
+    effect.render(&mut frame);
+    frame
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clock_runs_at_the_speed_rate() {
+        let mut clock = EffectClock::default();
+        clock.start(Instant::from_millis(1000));
+        let at = |ms: u32| Instant::from_millis(1000 + ms);
+        assert_eq!(
+            clock.tick(at(100), Speed::Normal),
+            Duration::from_millis(100)
+        );
+        // A speed change only applies from the last tick on, the effect time doesn't jump
+        assert_eq!(
+            clock.tick(at(200), Speed::Fastest),
+            Duration::from_millis(500)
+        );
+        assert_eq!(
+            clock.tick(at(600), Speed::Slowest),
+            Duration::from_millis(600)
+        );
+        assert_eq!(clock.elapsed(), Duration::from_millis(600));
+    }
+
+    #[test]
+    fn ids_round_trip() {
+        for id in EffectId::enumerate() {
+            assert_eq!(EffectId::from_u8(u8::from(*id)), Some(*id));
+            assert_eq!(id.effect().id(), *id);
+        }
+        assert_eq!(EffectId::from_u8(EffectId::COUNT as u8), None);
+    }
+}
@@ -0,0 +1,200 @@
+use crate::{
+    Duration, Effect, EffectClock, EffectContext, RandomColorGen, White, MAX_NUM_LEDS, RGBW8,
+};
+
+/// Warm white and amber candle light, each pixel flickers on its own
+/// around a slowly swaying flame, now and then guttering down low
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub struct Candle {
+    clock: EffectClock,
+    last_step: Duration,
+    flames: [Flame; MAX_NUM_LEDS],
+}
+
+impl Candle {
+    /// The flicker moves once per step
+    pub const STEP: Duration = Duration::from_millis(30);
+    /// One in this many steps a flame gutters
+    pub const GUTTER_CHANCE: u32 = 400;
+}
+
+/// Brightness levels, perceptual like the colors
+const FLICKER_MIN: u32 = 150;
+const FLICKER_MAX: u32 = 255;
+const GUTTER_MIN: u32 = 50;
+const GUTTER_MAX: u32 = 100;
+/// Largest offset of the low-frequency sway
+const SWAY_MAX: i16 = 40;
+
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+struct Flame {
+    level: u8,
+    target: u8,
+    sway: i16,
+    sway_target: i16,
+}
+
+impl Flame {
+    fn step(&mut self, rng: &mut RandomColorGen) {
+        if self.level == self.target {
+            self.target = if rng.rand_range(0..Candle::GUTTER_CHANCE) == 0 {
+                rng.rand_range(GUTTER_MIN..GUTTER_MAX)
+            } else {
+                rng.rand_range(FLICKER_MIN..FLICKER_MAX + 1)
+            } as u8;
+        }
+        // Quick flicker, a third of the way there per step
+        let delta = (self.target as i16 - self.level as i16) / 3;
+        self.level = if delta == 0 {
+            self.target
+        } else {
+            (self.level as i16 + delta) as u8
+        };
+
+        // Slow sway, one level per step
+        if self.sway == self.sway_target {
+            self.sway_target = rng.rand_range(0..(2 * SWAY_MAX as u32) + 1) as i16 - SWAY_MAX;
+        }
+        self.sway += (self.sway_target - self.sway).signum();
+    }
+
+    fn color(&self) -> RGBW8 {
+        let level = (self.level as i16 - SWAY_MAX + self.sway).max(0) as u16;
+        // White drops off faster than red, a dim flame turns amber
+        let r = level;
+        let w = (level * level / 255) * 3 / 4;
+        RGBW8::new_alpha(r as u8, 0, 0, White(w as u8))
+    }
+}
+
+impl Effect for Candle {
+    fn init(&mut self, ctx: &mut EffectContext, _from: RGBW8) {
+        self.clock.start(ctx.now);
+        self.last_step = Duration::ZERO;
+        for flame in self.flames.iter_mut() {
+            flame.level = ctx.rng.rand_range(FLICKER_MIN..FLICKER_MAX + 1) as u8;
+            flame.target = flame.level;
+            flame.sway = 0;
+            flame.sway_target = 0;
+        }
+    }
+
+    fn tick(&mut self, ctx: &mut EffectContext) -> bool {
+        let now = self.clock.tick(ctx.now, ctx.speed);
+        let mut stepped = false;
+        while now.duration_since(self.last_step) >= Self::STEP {
+            self.last_step = self.last_step + Self::STEP;
+            self.flames.iter_mut().for_each(|f| f.step(ctx.rng));
+            stepped = true;
+        }
+        stepped
+    }
+
+    fn render(&self, frame: &mut [RGBW8]) {
+        for (pixel, flame) in frame.iter_mut().zip(self.flames.iter()) {
+            *pixel = flame.color();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::render_at;
+    use super::*;
+    use crate::{ControllerConfig, Instant, Speed};
+
+    fn render(seed: u64, ms: u32) -> [RGBW8; 12] {
+        render_at::<Candle>(seed, Speed::Normal, Duration::from_millis(ms))
+    }
+
+    /// A candle started at 0 ms, ticked every `interval_ms` up to `ms`
+    fn run(seed: u64, ms: u32, interval_ms: u32) -> Candle {
+        let config = ControllerConfig::default();
+        let mut rng = RandomColorGen::new(seed);
+        let mut ctx = EffectContext {
+            now: Instant::from_millis(0),
+            config: &config,
+            speed: Speed::Normal,
+            rng: &mut rng,
+        };
+        let mut candle = Candle::default();
+        candle.init(&mut ctx, RGBW8::default());
+        for t in (interval_ms..=ms).step_by(interval_ms as usize) {
+            ctx.now = Instant::from_millis(t);
+            candle.tick(&mut ctx);
+        }
+        candle
+    }
+
+    #[test]
+    fn same_seed_replays_the_same_flicker() {
+        // Separate instances and generators, one ticked every update, one catching up at once
+        let every_update = run(7, 3000, 5);
+        let at_once = run(7, 3000, 3000);
+        assert_eq!(every_update, at_once);
+        let mut frame = [RGBW8::default(); 12];
+        every_update.render(&mut frame);
+        assert_eq!(frame, render(7, 3000));
+        assert_ne!(frame, render(7, 0));
+    }
+
+    #[test]
+    fn different_seeds_diverge() {
+        for seed in 0..8 {
+            for other in (seed + 1)..8 {
+                assert_ne!(
+                    render(seed, 1000),
+                    render(other, 1000),
+                    "{} {}",
+                    seed,
+                    other
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn red_and_white_only() {
+        for seed in 0..8 {
+            for ms in [0, 30, 1000, 60 * 1000] {
+                for pixel in render(seed, ms).iter() {
+                    assert_eq!((pixel.g, pixel.b), (0, 0));
+                    assert!(pixel.r >= pixel.a.0);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn pixels_flicker_on_their_own() {
+        let start = render(3, 0);
+        let later = render(3, 1000);
+        assert_ne!(start, later);
+        for frame in [start, later] {
+            assert!(frame.iter().any(|p| *p != frame[0]));
+        }
+        let changed = start.iter().zip(later.iter()).filter(|(a, b)| a != b);
+        assert!(changed.count() > 1);
+    }
+
+    #[test]
+    fn flames_gutter_down_now_and_then() {
+        let mut rng = RandomColorGen::new(1);
+        let mut flame = Flame {
+            level: FLICKER_MAX as u8,
+            target: FLICKER_MAX as u8,
+            ..Default::default()
+        };
+        let mut guttered = 0;
+        for _ in 0..(100 * Candle::GUTTER_CHANCE) {
+            flame.step(&mut rng);
+            let level = flame.level as u32;
+            assert!((GUTTER_MIN..=FLICKER_MAX).contains(&level), "{}", level);
+            if level < GUTTER_MAX {
+                guttered += 1;
+            }
+            assert!(flame.sway.abs() <= SWAY_MAX);
+        }
+        assert!(guttered > 0);
+    }
+}
@@ -0,0 +1,78 @@
+use crate::{Duration, Effect, EffectClock, EffectContext, White, RGBW, RGBW8};
+
+/// Pixels light up one per step until the ring is full, then clear the same way
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub struct ColorWipe {
+    clock: EffectClock,
+}
+
+impl ColorWipe {
+    pub const COLOR: RGBW8 = RGBW {
+        r: 0,
+        g: 255,
+        b: 64,
+        a: White(0),
+    };
+    pub const STEP: Duration = Duration::from_millis(100);
+}
+
+impl Effect for ColorWipe {
+    fn init(&mut self, ctx: &mut EffectContext, _from: RGBW8) {
+        self.clock.start(ctx.now);
+    }
+
+    fn tick(&mut self, ctx: &mut EffectContext) -> bool {
+        self.clock.tick(ctx.now, ctx.speed);
+        true
+    }
+
+    fn render(&self, frame: &mut [RGBW8]) {
+        let num_pixels = frame.len() as u32;
+        let count = (self.clock.elapsed().as_millis() / Self::STEP.as_millis()) % (2 * num_pixels);
+        for (i, pixel) in frame.iter_mut().enumerate() {
+            let lit = if count < num_pixels {
+                (i as u32) <= count
+            } else {
+                (i as u32) > count - num_pixels
+            };
+            *pixel = if lit { Self::COLOR } else { RGBW8::default() };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::render_at;
+    use super::*;
+    use crate::Speed;
+
+    /// Number of lit pixels, checking they're contiguous from `first`
+    fn lit(ms: u32, first: usize) -> usize {
+        let frame = render_at::<ColorWipe>(1, Speed::Normal, Duration::from_millis(ms));
+        let count = frame.iter().filter(|p| **p == ColorWipe::COLOR).count();
+        for (i, pixel) in frame.iter().enumerate() {
+            let expected = if i >= first && i < first + count {
+                ColorWipe::COLOR
+            } else {
+                RGBW8::default()
+            };
+            assert_eq!(*pixel, expected, "{} ms, pixel {}", ms, i);
+        }
+        count
+    }
+
+    #[test]
+    fn fills_then_clears() {
+        let step = ColorWipe::STEP.as_millis();
+        assert_eq!(lit(0, 0), 1);
+        assert_eq!(lit(step - 1, 0), 1);
+        assert_eq!(lit(5 * step + 50, 0), 6);
+        assert_eq!(lit(11 * step, 0), 12);
+        // Clearing starts from the first pixel
+        assert_eq!(lit(12 * step, 1), 11);
+        assert_eq!(lit(18 * step, 7), 5);
+        assert_eq!(lit(23 * step, 12), 0);
+        // And starts over
+        assert_eq!(lit(24 * step, 0), 1);
+    }
+}
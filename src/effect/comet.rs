@@ -0,0 +1,95 @@
+use super::SUBPIXELS;
+use crate::{Duration, Effect, EffectClock, EffectContext, White, RGBW, RGBW8};
+
+/// A head going around the ring once per period, with a fading tail
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub struct Comet {
+    clock: EffectClock,
+}
+
+impl Comet {
+    pub const COLOR: RGBW8 = RGBW {
+        r: 0,
+        g: 96,
+        b: 255,
+        a: White(64),
+    };
+    pub const PERIOD: Duration = Duration::from_millis(1500);
+    /// Length of the tail in pixels
+    pub const TAIL: u32 = 6;
+}
+
+impl Effect for Comet {
+    fn init(&mut self, ctx: &mut EffectContext, _from: RGBW8) {
+        self.clock.start(ctx.now);
+    }
+
+    fn tick(&mut self, ctx: &mut EffectContext) -> bool {
+        self.clock.tick(ctx.now, ctx.speed);
+        true
+    }
+
+    fn render(&self, frame: &mut [RGBW8]) {
+        let period = Self::PERIOD.as_millis();
+        let ring = frame.len() as u32 * SUBPIXELS;
+        let head = (self.clock.elapsed().as_millis() % period) * ring / period;
+        let tail = Self::TAIL * SUBPIXELS;
+        for (i, pixel) in frame.iter_mut().enumerate() {
+            let behind = (head + ring - (i as u32 * SUBPIXELS)) % ring;
+            *pixel = if behind < tail {
+                scale(&Self::COLOR, (255 - (behind * 255 / tail)) as u8)
+            } else {
+                RGBW8::default()
+            };
+        }
+    }
+}
+
+/// Perceptual scaling, the driver's lightness correction keeps the tail smooth
+fn scale(color: &RGBW8, level: u8) -> RGBW8 {
+    let scale = |c: u8| ((c as u16 * level as u16) / 255) as u8;
+    RGBW8::new_alpha(
+        scale(color.r),
+        scale(color.g),
+        scale(color.b),
+        White(scale(color.a.0)),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::render_at;
+    use super::*;
+    use crate::Speed;
+
+    fn render(ms: u32) -> [RGBW8; 12] {
+        render_at::<Comet>(1, Speed::Normal, Duration::from_millis(ms))
+    }
+
+    #[test]
+    fn head_and_fading_tail() {
+        let frame = render(0);
+        assert_eq!(frame[0], Comet::COLOR);
+        // The tail trails behind the head, pixels 11 down to 7
+        let tail = [frame[11], frame[10], frame[9], frame[8], frame[7]];
+        let mut brighter = Comet::COLOR;
+        for pixel in tail.iter() {
+            assert_ne!(*pixel, RGBW8::default());
+            assert!(pixel.b < brighter.b);
+            brighter = *pixel;
+        }
+        assert_eq!(frame[11], scale(&Comet::COLOR, 213));
+        assert!(frame[1..7].iter().all(|p| *p == RGBW8::default()));
+    }
+
+    #[test]
+    fn moves_one_pixel_per_step() {
+        let start = render(0);
+        // 12 pixels per period
+        let frame = render(2 * Comet::PERIOD.as_millis() / 12);
+        for i in 0..12 {
+            assert_eq!(frame[(i + 2) % 12], start[i], "{}", i);
+        }
+        assert_eq!(render(Comet::PERIOD.as_millis()), start);
+    }
+}
@@ -0,0 +1,36 @@
+use crate::{fill, Crossfade, Effect, EffectClock, EffectContext, RGBW8};
+use log::debug;
+
+/// Fades between random colors of the rainbow
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub struct Fade {
+    clock: EffectClock,
+    fade: Crossfade,
+}
+
+impl Effect for Fade {
+    fn init(&mut self, ctx: &mut EffectContext, from: RGBW8) {
+        self.clock.start(ctx.now);
+        let next_color = ctx.rng.next_rand_rgb(from);
+        self.fade.start(from, next_color, self.clock.elapsed());
+    }
+
+    fn tick(&mut self, ctx: &mut EffectContext) -> bool {
+        let now = self.clock.tick(ctx.now, ctx.speed);
+        let changed = self.fade.update(
+            now,
+            ctx.config.fade_mode_fade_duration,
+            ctx.config.fade_mode_interpolation,
+        );
+        if self.fade.destination_color_reached() {
+            let next_color = ctx.rng.next_rand_rgb(self.fade.color);
+            debug!("Next color (Fade) {:?}", next_color);
+            self.fade.fade_to(next_color, now);
+        }
+        changed
+    }
+
+    fn render(&self, frame: &mut [RGBW8]) {
+        fill(frame, &self.fade.color);
+    }
+}
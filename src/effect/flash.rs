@@ -0,0 +1,33 @@
+use crate::{fill, Duration, Effect, EffectClock, EffectContext, RGBW8};
+
+/// Instant jumps between random basic colors,
+/// each held for `ControllerConfig::flash_mode_hold_duration`
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub struct Flash {
+    clock: EffectClock,
+    color: RGBW8,
+    changed_at: Duration,
+}
+
+impl Effect for Flash {
+    fn init(&mut self, ctx: &mut EffectContext, from: RGBW8) {
+        self.clock.start(ctx.now);
+        self.color = ctx.rng.next_rand_color(from).as_rgbw();
+        self.changed_at = self.clock.elapsed();
+    }
+
+    fn tick(&mut self, ctx: &mut EffectContext) -> bool {
+        let now = self.clock.tick(ctx.now, ctx.speed);
+        if now.duration_since(self.changed_at) >= ctx.config.flash_mode_hold_duration {
+            self.color = ctx.rng.next_rand_color(self.color).as_rgbw();
+            self.changed_at = now;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn render(&self, frame: &mut [RGBW8]) {
+        fill(frame, &self.color);
+    }
+}
@@ -0,0 +1,80 @@
+use super::SUBPIXELS;
+use crate::{Duration, Effect, EffectClock, EffectContext, White, RGBW8};
+use colorous::RAINBOW as PALETTE;
+
+/// Rainbow spread around the ring, one revolution per period
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub struct RainbowChase {
+    clock: EffectClock,
+}
+
+impl RainbowChase {
+    pub const PERIOD: Duration = Duration::from_millis(4 * 1000);
+}
+
+impl Effect for RainbowChase {
+    fn init(&mut self, ctx: &mut EffectContext, _from: RGBW8) {
+        self.clock.start(ctx.now);
+    }
+
+    fn tick(&mut self, ctx: &mut EffectContext) -> bool {
+        self.clock.tick(ctx.now, ctx.speed);
+        true
+    }
+
+    fn render(&self, frame: &mut [RGBW8]) {
+        let num_pixels = frame.len() as u32;
+        let period = Self::PERIOD.as_millis();
+        let offset = (self.clock.elapsed().as_millis() % period) * SUBPIXELS / period;
+        for (i, pixel) in frame.iter_mut().enumerate() {
+            let position = ((i as u32 * SUBPIXELS / num_pixels) + offset) % SUBPIXELS;
+            let (r, g, b) = PALETTE
+                .eval_rational(position as _, SUBPIXELS as _)
+                .into_tuple();
+            *pixel = RGBW8::new_alpha(r, g, b, White(0));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::render_at;
+    use super::*;
+    use crate::Speed;
+
+    fn render(ms: u32) -> [RGBW8; 12] {
+        render_at::<RainbowChase>(1, Speed::Normal, Duration::from_millis(ms))
+    }
+
+    #[test]
+    fn rainbow_around_the_ring() {
+        let frame = render(0);
+        let (r, g, b) = PALETTE.eval_rational(0, SUBPIXELS as _).into_tuple();
+        assert_eq!(frame[0], RGBW8::new_alpha(r, g, b, White(0)));
+        for (i, pixel) in frame.iter().enumerate().skip(1) {
+            assert_ne!(*pixel, frame[0], "{}", i);
+        }
+        assert!(frame.iter().all(|p| p.a == White(0)));
+    }
+
+    #[test]
+    fn one_revolution_per_period() {
+        let start = render(0);
+        // A quarter period is 3 of the 12 pixels
+        let quarter = render(RainbowChase::PERIOD.as_millis() / 4);
+        for i in 0..12 {
+            assert_eq!(quarter[i], start[(i + 3) % 12], "{}", i);
+        }
+        assert_eq!(render(RainbowChase::PERIOD.as_millis()), start);
+    }
+
+    #[test]
+    fn speed_scales_the_period() {
+        let fastest = render_at::<RainbowChase>(
+            1,
+            Speed::Fastest,
+            Duration::from_millis(RainbowChase::PERIOD.as_millis() / 16),
+        );
+        assert_eq!(fastest, render(RainbowChase::PERIOD.as_millis() / 4));
+    }
+}
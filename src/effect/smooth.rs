@@ -0,0 +1,36 @@
+use crate::{fill, Crossfade, Effect, EffectClock, EffectContext, RGBW8};
+use log::debug;
+
+/// Fades between random basic colors
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub struct Smooth {
+    clock: EffectClock,
+    fade: Crossfade,
+}
+
+impl Effect for Smooth {
+    fn init(&mut self, ctx: &mut EffectContext, from: RGBW8) {
+        self.clock.start(ctx.now);
+        let next_color = ctx.rng.next_rand_color(from).as_rgbw();
+        self.fade.start(from, next_color, self.clock.elapsed());
+    }
+
+    fn tick(&mut self, ctx: &mut EffectContext) -> bool {
+        let now = self.clock.tick(ctx.now, ctx.speed);
+        let changed = self.fade.update(
+            now,
+            ctx.config.smooth_mode_fade_duration,
+            ctx.config.smooth_mode_interpolation,
+        );
+        if self.fade.destination_color_reached() {
+            let next_color = ctx.rng.next_rand_color(self.fade.color).as_rgbw();
+            debug!("Next color (Smooth) {:?}", next_color);
+            self.fade.fade_to(next_color, now);
+        }
+        changed
+    }
+
+    fn render(&self, frame: &mut [RGBW8]) {
+        fill(frame, &self.fade.color);
+    }
+}
@@ -0,0 +1,107 @@
+use crate::{
+    fill, Effect, EffectClock, EffectContext, White, COLOR_OFF, MIN_STROBE_PHASE, RGBW, RGBW8,
+};
+
+/// Hard cuts between fully on and off,
+/// at `ControllerConfig::strobe_mode_frequency_hz` and duty cycle
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub struct Strobe {
+    clock: EffectClock,
+    color: RGBW8,
+}
+
+impl Strobe {
+    /// The on phase, every channel fully on, scaled by the brightness like any color
+    pub const COLOR: RGBW8 = RGBW {
+        r: 255,
+        g: 255,
+        b: 255,
+        a: White(255),
+    };
+}
+
+impl Effect for Strobe {
+    fn init(&mut self, ctx: &mut EffectContext, _from: RGBW8) {
+        self.clock.start(ctx.now);
+        self.color = Self::COLOR;
+    }
+
+    fn tick(&mut self, ctx: &mut EffectContext) -> bool {
+        let elapsed = self.clock.tick(ctx.now, ctx.speed).as_millis();
+        let (period, on_time) = ctx.config.strobe_timing();
+        let period = period.as_millis();
+        // Faster speeds shrink both phases, keep each one at least an update long
+        let min_phase = MIN_STROBE_PHASE.as_millis() * ctx.speed.rate() / 4;
+        let on_time = on_time
+            .as_millis()
+            .max(min_phase)
+            .min(period.saturating_sub(min_phase));
+        let prev_color = self.color;
+        self.color = if elapsed % period < on_time {
+            Self::COLOR
+        } else {
+            COLOR_OFF
+        };
+        self.color != prev_color
+    }
+
+    fn render(&self, frame: &mut [RGBW8]) {
+        fill(frame, &self.color);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ControllerConfig, Duration, Instant, RandomColorGen, Speed};
+
+    const ON: RGBW8 = Strobe::COLOR;
+
+    /// The strobe color on each controller update, 5 ms apart
+    fn colors(config: &ControllerConfig, speed: Speed, updates: usize) -> [RGBW8; 16] {
+        let mut rng = RandomColorGen::new(1);
+        let mut ctx = EffectContext {
+            now: Instant::from_millis(0),
+            config,
+            speed,
+            rng: &mut rng,
+        };
+        let mut strobe = Strobe::default();
+        strobe.init(&mut ctx, COLOR_OFF);
+        let mut colors = [COLOR_OFF; 16];
+        for color in colors.iter_mut().take(updates) {
+            strobe.tick(&mut ctx);
+            *color = strobe.color;
+            ctx.now = ctx.now + Duration::from_millis(5);
+        }
+        colors
+    }
+
+    #[test]
+    fn duty_cycle() {
+        let config = ControllerConfig {
+            strobe_mode_frequency_hz: 20,
+            strobe_mode_duty_percent: 25,
+            ..Default::default()
+        };
+        // 50 ms period, 12 ms on
+        let colors = colors(&config, Speed::Normal, 10);
+        assert_eq!(&colors[..3], &[ON, ON, ON]);
+        assert!(colors[3..10].iter().all(|c| *c == COLOR_OFF));
+    }
+
+    #[test]
+    fn fastest_speed_keeps_both_phases_an_update_long() {
+        let config = ControllerConfig {
+            strobe_mode_frequency_hz: 25,
+            strobe_mode_duty_percent: 13,
+            ..Default::default()
+        };
+        assert_eq!(config.validate(), Ok(()));
+        // 10 ms period at 4x, the 1.25 ms on time would mostly fall between updates
+        let colors = colors(&config, Speed::Fastest, 16);
+        for pair in colors.chunks(2) {
+            assert_eq!(pair, &[ON, COLOR_OFF]);
+        }
+    }
+}
@@ -0,0 +1,85 @@
+use crate::{Duration, Effect, EffectClock, EffectContext, White, RGBW, RGBW8};
+
+/// Every third pixel lit, moving one pixel per step
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub struct TheaterChase {
+    clock: EffectClock,
+}
+
+impl TheaterChase {
+    pub const COLOR: RGBW8 = RGBW {
+        r: 255,
+        g: 64,
+        b: 0,
+        a: White(0),
+    };
+    pub const STEP: Duration = Duration::from_millis(150);
+}
+
+impl Effect for TheaterChase {
+    fn init(&mut self, ctx: &mut EffectContext, _from: RGBW8) {
+        self.clock.start(ctx.now);
+    }
+
+    fn tick(&mut self, ctx: &mut EffectContext) -> bool {
+        self.clock.tick(ctx.now, ctx.speed);
+        true
+    }
+
+    fn render(&self, frame: &mut [RGBW8]) {
+        let offset = (self.clock.elapsed().as_millis() / Self::STEP.as_millis()) % 3;
+        for (i, pixel) in frame.iter_mut().enumerate() {
+            *pixel = if i as u32 % 3 == offset {
+                Self::COLOR
+            } else {
+                RGBW8::default()
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::render_at;
+    use super::*;
+    use crate::Speed;
+
+    fn render(speed: Speed, ms: u32) -> [RGBW8; 12] {
+        render_at::<TheaterChase>(1, speed, Duration::from_millis(ms))
+    }
+
+    fn lit(frame: &[RGBW8; 12]) -> [bool; 12] {
+        let mut lit = [false; 12];
+        for (l, p) in lit.iter_mut().zip(frame.iter()) {
+            *l = *p == TheaterChase::COLOR;
+            assert!(*l || *p == RGBW8::default());
+        }
+        lit
+    }
+
+    #[test]
+    fn every_third_pixel_moving_one_per_step() {
+        let step = TheaterChase::STEP.as_millis();
+        for (ms, offset) in [
+            (0, 0),
+            (step - 1, 0),
+            (step, 1),
+            (2 * step, 2),
+            (3 * step, 0),
+        ] {
+            let lit = lit(&render(Speed::Normal, ms));
+            for (i, l) in lit.iter().enumerate() {
+                assert_eq!(*l, i % 3 == offset, "{} ms, pixel {}", ms, i);
+            }
+        }
+    }
+
+    #[test]
+    fn speed_scales_the_step() {
+        let step = TheaterChase::STEP.as_millis();
+        let two_steps = render(Speed::Normal, 2 * step);
+        assert_ne!(two_steps, render(Speed::Normal, 0));
+        assert_eq!(render(Speed::Fastest, 2 * step / 4), two_steps);
+        assert_eq!(render(Speed::Slowest, 2 * step * 4), two_steps);
+    }
+}
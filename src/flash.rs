@@ -0,0 +1,92 @@
+use crate::hal::pac::{flash::RegisterBlock, FLASH};
+use crate::{FlashError, SettingsFlash, SETTINGS_NUM_PAGES, SETTINGS_PAGE_SIZE};
+use core::ptr;
+
+/// Start of the settings pages, the last pages of the 256K flash, excluded in memory.x
+pub const SETTINGS_FLASH_ADDRESS: usize = 0x0804_0000 - (SETTINGS_NUM_PAGES * SETTINGS_PAGE_SIZE);
+
+const KEY1: u32 = 0x4567_0123;
+const KEY2: u32 = 0xCDEF_89AB;
+
+/// The settings pages of the on-chip flash
+pub struct InternalFlash(());
+
+impl InternalFlash {
+    /// # Safety
+    /// The FLASH peripheral is owned by the hal for the ACR register,
+    /// only one of these may exist and it only uses the program/erase registers
+    pub unsafe fn new() -> Self {
+        InternalFlash(())
+    }
+
+    fn regs(&self) -> &RegisterBlock {
+        unsafe { &*FLASH::ptr() }
+    }
+
+    fn unlock(&mut self) {
+        if self.regs().cr.read().lock().is_locked() {
+            self.regs().keyr.write(|w| w.fkeyr().bits(KEY1));
+            self.regs().keyr.write(|w| w.fkeyr().bits(KEY2));
+        }
+    }
+
+    fn lock(&mut self) {
+        self.regs().cr.modify(|_, w| w.lock().set_bit());
+    }
+
+    fn wait_and_check(&mut self) -> Result<(), FlashError> {
+        while self.regs().sr.read().bsy().is_active() {}
+        let sr = self.regs().sr.read();
+        let result = if sr.wrprterr().is_error() {
+            Err(FlashError::WriteProtected)
+        } else if sr.pgerr().is_error() {
+            Err(FlashError::Program)
+        } else {
+            Ok(())
+        };
+        // Write 1 to clear
+        self.regs()
+            .sr
+            .write(|w| w.eop().set_bit().wrprterr().set_bit().pgerr().set_bit());
+        result
+    }
+}
+
+impl SettingsFlash for InternalFlash {
+    fn read(&mut self, offset: usize, buf: &mut [u8]) {
+        let addr = (SETTINGS_FLASH_ADDRESS + offset) as *const u8;
+        for (i, b) in buf.iter_mut().enumerate() {
+            *b = unsafe { ptr::read_volatile(addr.add(i)) };
+        }
+    }
+
+    fn erase_page(&mut self, page: usize) -> Result<(), FlashError> {
+        let addr = SETTINGS_FLASH_ADDRESS + (page * SETTINGS_PAGE_SIZE);
+        self.unlock();
+        self.regs().cr.modify(|_, w| w.per().set_bit());
+        self.regs().ar.write(|w| w.far().bits(addr as u32));
+        self.regs().cr.modify(|_, w| w.strt().set_bit());
+        let result = self.wait_and_check();
+        self.regs().cr.modify(|_, w| w.per().clear_bit());
+        self.lock();
+        result
+    }
+
+    fn program(&mut self, offset: usize, data: &[u8]) -> Result<(), FlashError> {
+        let addr = (SETTINGS_FLASH_ADDRESS + offset) as *mut u16;
+        self.unlock();
+        self.regs().cr.modify(|_, w| w.pg().set_bit());
+        let mut result = Ok(());
+        for (i, half_word) in data.chunks_exact(2).enumerate() {
+            let half_word = u16::from_le_bytes([half_word[0], half_word[1]]);
+            unsafe { ptr::write_volatile(addr.add(i), half_word) };
+            result = self.wait_and_check();
+            if result.is_err() {
+                break;
+            }
+        }
+        self.regs().cr.modify(|_, w| w.pg().clear_bit());
+        self.lock();
+        result
+    }
+}
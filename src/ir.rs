@@ -1,29 +1,307 @@
+use core::convert::Infallible;
 use core::fmt;
+use embedded_hal::digital::v2::InputPin;
 use hal::time::Hertz;
 use heapless::{consts::U8, spsc};
-use infrared::protocols::nec::Nec16Command;
-use infrared::{protocols::nec::Nec16, PeriodicReceiver};
+use infrared::hal::PeriodicReceiver4;
+use infrared::protocols::nec::{Nec16Command, NecSamsungCommand};
+use infrared::protocols::{Nec16, NecSamsung, Rc5, Rc6};
 
 pub const IR_SAMPLE_RATE: Hertz = Hertz(20_000);
 
-pub type IrReceiver<RecvrPin> = PeriodicReceiver<Nec16, RecvrPin>;
+pub const MAX_IR_ADDRESSES: usize = 4;
 
-pub struct IrCommandQueue(spsc::Queue<IrCommand, U8, u8, spsc::SingleCore>);
+/// The stock 24 key remote, NEC address 0 sent with its inverse
+pub const STOCK_REMOTE: IrAddress = IrAddress {
+    protocol: IrProtocol::Nec,
+    address: 0x00,
+};
 
-impl IrCommandQueue {
+/// Every supported protocol at once, standard NEC is decoded as 16 bit NEC
+pub type IrReceiver<RecvrPin> = PeriodicReceiver4<Nec16, NecSamsung, Rc5, Rc6, RecvrPin>;
+
+/// Decodes frames from any supported remote, dropping the ones the filter doesn't accept
+pub struct IrDecoder<RecvrPin> {
+    recvr: IrReceiver<RecvrPin>,
+    /// RC5 and RC6 flip a toggle bit on each press, the same bit again is a repeat
+    last_toggle: Option<(IrKey, bool)>,
+    filter: IrAddressFilter,
+    /// Frames dropped by the filter, wraps around
+    rejected: u32,
+    /// Last key pressed that was dropped, not yet reported
+    last_rejected: Option<IrKey>,
+}
+
+impl<RecvrPin> IrDecoder<RecvrPin>
+where
+    RecvrPin: InputPin<Error = Infallible>,
+{
+    pub fn new(pin: RecvrPin) -> Self {
+        IrDecoder {
+            recvr: IrReceiver::new(pin, IR_SAMPLE_RATE.0),
+            last_toggle: None,
+            filter: IrAddressFilter::new(),
+            rejected: 0,
+            last_rejected: None,
+        }
+    }
+
+    pub fn set_filter(&mut self, filter: IrAddressFilter) {
+        self.filter = filter;
+    }
+
+    /// Call this at `IR_SAMPLE_RATE`, returns the frames the filter accepts
+    pub fn poll(&mut self) -> Option<IrFrame> {
+        let frame = self.decode()?;
+        self.filter(frame)
+    }
+
+    /// Counts the frames dropped, remembers the last key pressed among them
+    fn filter(&mut self, frame: IrFrame) -> Option<IrFrame> {
+        if self.filter.accepts(&frame.key) {
+            Some(frame)
+        } else {
+            self.rejected = self.rejected.wrapping_add(1);
+            if !frame.repeat {
+                self.last_rejected = Some(frame.key);
+            }
+            None
+        }
+    }
+
+    /// The last key pressed that was rejected since the previous call,
+    /// and the number of frames rejected so far, repeats included
+    pub fn take_rejected(&mut self) -> Option<(IrKey, u32)> {
+        self.last_rejected.take().map(|key| (key, self.rejected))
+    }
+
+    fn decode(&mut self) -> Option<IrFrame> {
+        // Unwrap ok, infallible
+        let (nec, samsung, rc5, rc6) = self.recvr.poll().unwrap();
+        if let Some(c) = nec {
+            Some(c.into())
+        } else if let Some(c) = samsung {
+            Some(c.into())
+        } else if let Some(c) = rc5 {
+            Some(self.toggled(IrProtocol::Rc5, c.addr, c.cmd, c.toggle))
+        } else {
+            rc6.map(|c| self.toggled(IrProtocol::Rc6, c.addr, c.cmd, c.toggle))
+        }
+    }
+
+    fn toggled(&mut self, protocol: IrProtocol, address: u8, command: u8, toggle: bool) -> IrFrame {
+        let key = IrKey {
+            protocol,
+            address: address.into(),
+            command,
+        };
+        let repeat = self.last_toggle == Some((key, toggle));
+        self.last_toggle = Some((key, toggle));
+        IrFrame { key, repeat }
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub enum IrProtocol {
+    /// 8 bit address, sent with its inverse
+    Nec,
+    Nec16,
+    /// NEC with Samsung timing
+    Samsung,
+    Rc5,
+    Rc6,
+}
+
+impl IrProtocol {
+    pub fn from_u8(value: u8) -> Option<Self> {
+        use IrProtocol::*;
+        Some(match value {
+            0 => Nec,
+            1 => Nec16,
+            2 => Samsung,
+            3 => Rc5,
+            4 => Rc6,
+            _ => return None,
+        })
+    }
+}
+
+impl From<IrProtocol> for u8 {
+    fn from(p: IrProtocol) -> Self {
+        p as u8
+    }
+}
+
+pub struct IrFrameQueue(spsc::Queue<IrFrame, U8, u8, spsc::SingleCore>);
+
+impl IrFrameQueue {
     pub const fn new() -> Self {
-        IrCommandQueue(spsc::Queue(unsafe { heapless::i::Queue::u8_sc() }))
+        IrFrameQueue(spsc::Queue(unsafe { heapless::i::Queue::u8_sc() }))
     }
 
-    pub fn dequeue(&mut self) -> Option<IrCommand> {
+    pub fn dequeue(&mut self) -> Option<IrFrame> {
         self.0.dequeue()
     }
 
-    pub fn enqueue(&mut self, item: IrCommand) -> Result<(), IrCommand> {
+    pub fn enqueue(&mut self, item: IrFrame) -> Result<(), IrFrame> {
         self.0.enqueue(item)
     }
 }
 
+/// Remote addresses accepted by the `IrDecoder`, the stock remote to start with.
+/// Accepting every remote has to be asked for with `set_any`
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct IrAddressFilter {
+    addresses: [Option<IrAddress>; MAX_IR_ADDRESSES],
+    any: bool,
+}
+
+impl Default for IrAddressFilter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl IrAddressFilter {
+    /// Only the stock remote
+    pub const fn new() -> Self {
+        let mut filter = Self::empty();
+        filter.addresses[0] = Some(STOCK_REMOTE);
+        filter
+    }
+
+    /// No remote at all
+    pub const fn empty() -> Self {
+        IrAddressFilter {
+            addresses: [None; MAX_IR_ADDRESSES],
+            any: false,
+        }
+    }
+
+    /// Every remote, whatever addresses are listed
+    pub const fn any() -> Self {
+        IrAddressFilter {
+            addresses: [None; MAX_IR_ADDRESSES],
+            any: true,
+        }
+    }
+
+    /// No addresses listed, `any` aside
+    pub fn is_empty(&self) -> bool {
+        self.addresses.iter().all(|a| a.is_none())
+    }
+
+    pub fn is_any(&self) -> bool {
+        self.any
+    }
+
+    /// The addresses listed are kept, they apply again once cleared
+    pub fn set_any(&mut self, any: bool) {
+        self.any = any;
+    }
+
+    pub fn accepts(&self, key: &IrKey) -> bool {
+        self.any || self.contains(IrAddress::of(key))
+    }
+
+    pub fn contains(&self, address: IrAddress) -> bool {
+        self.addresses.contains(&Some(address))
+    }
+
+    /// Returns false if the filter is full
+    pub fn add(&mut self, address: IrAddress) -> bool {
+        if self.contains(address) {
+            return true;
+        }
+        match self.addresses.iter_mut().find(|a| a.is_none()) {
+            Some(a) => {
+                *a = Some(address);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// The rest move up, so the same addresses always compare equal
+    pub fn remove(&mut self, address: IrAddress) {
+        if let Some(i) = self.addresses.iter().position(|a| *a == Some(address)) {
+            self.addresses[i..].rotate_left(1);
+            self.addresses[MAX_IR_ADDRESSES - 1] = None;
+        }
+    }
+
+    pub fn addresses(&self) -> impl Iterator<Item = &IrAddress> {
+        self.addresses.iter().flatten()
+    }
+}
+
+/// A remote, protocols have their own address spaces
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub struct IrAddress {
+    pub protocol: IrProtocol,
+    pub address: u16,
+}
+
+impl IrAddress {
+    pub fn of(key: &IrKey) -> Self {
+        IrAddress {
+            protocol: key.protocol,
+            address: key.address,
+        }
+    }
+}
+
+/// What a remote sends for one of its keys, the same for every protocol
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub struct IrKey {
+    pub protocol: IrProtocol,
+    pub address: u16,
+    pub command: u8,
+}
+
+/// A decoded frame, mapped to a button by the `IrKeymap`
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub struct IrFrame {
+    pub key: IrKey,
+    pub repeat: bool,
+}
+
+impl From<Nec16Command> for IrFrame {
+    fn from(c: Nec16Command) -> Self {
+        let [low, high] = c.addr.to_le_bytes();
+        let key = if high == !low {
+            IrKey {
+                protocol: IrProtocol::Nec,
+                address: low.into(),
+                command: c.cmd,
+            }
+        } else {
+            IrKey {
+                protocol: IrProtocol::Nec16,
+                address: c.addr,
+                command: c.cmd,
+            }
+        };
+        IrFrame {
+            key,
+            repeat: c.repeat,
+        }
+    }
+}
+
+impl From<NecSamsungCommand> for IrFrame {
+    fn from(c: NecSamsungCommand) -> Self {
+        IrFrame {
+            key: IrKey {
+                protocol: IrProtocol::Samsung,
+                address: c.addr.into(),
+                command: c.cmd,
+            },
+            repeat: c.repeat,
+        }
+    }
+}
+
 #[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
 pub struct IrCommand {
     pub button: Button,
@@ -40,15 +318,6 @@ impl fmt::Display for IrCommand {
     }
 }
 
-impl From<Nec16Command> for IrCommand {
-    fn from(c: Nec16Command) -> Self {
-        IrCommand {
-            button: c.into(),
-            repeat: c.repeat,
-        }
-    }
-}
-
 #[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
 pub enum Button {
     BrightnessDown,
@@ -79,22 +348,41 @@ pub enum Button {
 }
 
 impl Button {
-    pub fn repeat_allowed(self) -> bool {
+    /// Every button of the remote, in the layout of its keys, row by row
+    pub fn enumerate() -> &'static [Self] {
         use Button::*;
-        matches!(self, BrightnessDown | BrightnessUp)
+        &[
+            BrightnessUp,
+            BrightnessDown,
+            Off,
+            On,
+            Red,
+            Green,
+            Blue,
+            White,
+            Red1,
+            Green1,
+            Blue1,
+            Flash,
+            Red2,
+            Green2,
+            Blue2,
+            Strobe,
+            Red3,
+            Green3,
+            Blue3,
+            Fade,
+            Red4,
+            Green4,
+            Blue4,
+            Smooth,
+        ]
     }
-}
 
-impl fmt::Display for Button {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{:?}", self)
-    }
-}
-
-impl From<Nec16Command> for Button {
-    fn from(c: Nec16Command) -> Self {
+    /// Button of the stock 24 key remote, any address
+    pub fn from_stock_command(command: u8) -> Self {
         use Button::*;
-        match c.cmd {
+        match command {
             4 => BrightnessDown,
             5 => BrightnessUp,
             6 => Off,
@@ -119,7 +407,190 @@ impl From<Nec16Command> for Button {
             25 => Red3,
             26 => Blue3,
             27 => Fade,
-            _ => Unknown(c.cmd),
+            _ => Unknown(command),
+        }
+    }
+
+    /// Brightness keeps stepping while held
+    pub fn repeat_allowed(self) -> bool {
+        use Button::*;
+        matches!(self, BrightnessDown | BrightnessUp)
+    }
+}
+
+impl fmt::Display for Button {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The decoder is only polled through `toggled` here
+    struct IdlePin;
+
+    impl InputPin for IdlePin {
+        type Error = Infallible;
+
+        fn is_high(&self) -> Result<bool, Infallible> {
+            Ok(true)
+        }
+
+        fn is_low(&self) -> Result<bool, Infallible> {
+            Ok(false)
+        }
+    }
+
+    fn nec16(addr: u16, repeat: bool) -> IrFrame {
+        Nec16Command {
+            addr,
+            cmd: 7,
+            repeat,
         }
+        .into()
+    }
+
+    fn key(protocol: IrProtocol, address: u16) -> IrKey {
+        IrKey {
+            protocol,
+            address,
+            command: 7,
+        }
+    }
+
+    #[test]
+    fn nec_address_sent_with_its_inverse_is_8_bit() {
+        assert_eq!(nec16(0xFF00, false).key, key(IrProtocol::Nec, 0x00));
+        assert_eq!(nec16(0xFB04, false).key, key(IrProtocol::Nec, 0x04));
+        assert_eq!(nec16(0x00FF, false).key, key(IrProtocol::Nec, 0xFF));
+        assert!(nec16(0xFB04, true).repeat);
+    }
+
+    #[test]
+    fn any_other_nec_address_is_16_bit() {
+        assert_eq!(nec16(0x1234, false).key, key(IrProtocol::Nec16, 0x1234));
+        // Off by one bit from an inverse pair
+        assert_eq!(nec16(0xFB05, false).key, key(IrProtocol::Nec16, 0xFB05));
+        assert_eq!(nec16(0x0000, false).key, key(IrProtocol::Nec16, 0x0000));
+        assert_eq!(nec16(0xFFFF, false).key, key(IrProtocol::Nec16, 0xFFFF));
+    }
+
+    #[test]
+    fn same_toggle_is_a_repeat() {
+        let mut decoder = IrDecoder::new(IdlePin);
+        let mut press = |toggle| decoder.toggled(IrProtocol::Rc5, 0, 7, toggle).repeat;
+        assert!(!press(false));
+        assert!(press(false));
+        assert!(press(false));
+        // The first frame after the toggle flips is a new press, then it repeats again
+        assert!(!press(true));
+        assert!(press(true));
+        assert!(!press(false));
+    }
+
+    #[test]
+    fn toggle_is_tracked_per_key() {
+        let mut decoder = IrDecoder::new(IdlePin);
+        assert!(!decoder.toggled(IrProtocol::Rc5, 0, 7, false).repeat);
+        // Another key, address or protocol with the same toggle isn't a repeat
+        assert!(!decoder.toggled(IrProtocol::Rc5, 0, 8, false).repeat);
+        assert!(!decoder.toggled(IrProtocol::Rc5, 1, 8, false).repeat);
+        assert!(!decoder.toggled(IrProtocol::Rc6, 1, 8, false).repeat);
+        assert!(decoder.toggled(IrProtocol::Rc6, 1, 8, false).repeat);
+        // Back to the first key, its toggle is no longer the last one seen
+        assert!(!decoder.toggled(IrProtocol::Rc5, 0, 7, false).repeat);
+        let frame = decoder.toggled(IrProtocol::Rc6, 2, 9, true);
+        assert_eq!(frame.key.address, 2);
+        assert_eq!(frame.key.command, 9);
+    }
+
+    fn address(protocol: IrProtocol, address: u16) -> IrAddress {
+        IrAddress { protocol, address }
+    }
+
+    #[test]
+    fn default_filter_is_the_stock_remote() {
+        let filter = IrAddressFilter::default();
+        assert!(!filter.is_any());
+        assert!(filter.addresses().eq([STOCK_REMOTE].iter()));
+        assert!(filter.accepts(&nec16(0xFF00, false).key));
+        assert!(!filter.accepts(&nec16(0xFB04, false).key));
+        assert!(!filter.accepts(&key(IrProtocol::Nec16, 0x00)));
+        assert!(!filter.accepts(&key(IrProtocol::Rc6, 0x00)));
+        assert!(!IrAddressFilter::empty().accepts(&key(IrProtocol::Nec, 0x00)));
+    }
+
+    #[test]
+    fn any_is_opted_into() {
+        let mut filter = IrAddressFilter::empty();
+        filter.set_any(true);
+        assert!(filter.accepts(&key(IrProtocol::Rc5, 20)));
+        assert!(filter.accepts(&key(IrProtocol::Samsung, 0x1234)));
+        assert_eq!(filter, IrAddressFilter::any());
+        // The list applies again
+        filter.set_any(false);
+        assert!(!filter.accepts(&key(IrProtocol::Rc5, 20)));
+    }
+
+    #[test]
+    fn accepts_listed_remotes_only() {
+        let mut filter = IrAddressFilter::empty();
+        assert!(filter.add(address(IrProtocol::Nec, 0)));
+        assert!(filter.add(address(IrProtocol::Rc5, 20)));
+        assert!(filter.accepts(&key(IrProtocol::Nec, 0)));
+        assert!(filter.accepts(&key(IrProtocol::Rc5, 20)));
+        assert!(!filter.accepts(&key(IrProtocol::Nec, 1)));
+        // Protocols have their own address spaces
+        assert!(!filter.accepts(&key(IrProtocol::Nec16, 0)));
+        assert!(!filter.accepts(&key(IrProtocol::Rc6, 20)));
+
+        filter.remove(address(IrProtocol::Nec, 0));
+        assert!(!filter.accepts(&key(IrProtocol::Nec, 0)));
+        filter.remove(address(IrProtocol::Rc5, 20));
+        assert!(filter.is_empty());
+        // Empty isn't any
+        assert!(!filter.accepts(&key(IrProtocol::Rc5, 20)));
+    }
+
+    #[test]
+    fn add_when_full() {
+        let mut filter = IrAddressFilter::empty();
+        for a in 0..MAX_IR_ADDRESSES as u16 {
+            assert!(filter.add(address(IrProtocol::Samsung, a)));
+        }
+        let extra = address(IrProtocol::Samsung, MAX_IR_ADDRESSES as u16);
+        assert!(!filter.add(extra));
+        assert!(!filter.accepts(&key(IrProtocol::Samsung, extra.address)));
+        // Already listed
+        assert!(filter.add(address(IrProtocol::Samsung, 0)));
+        assert_eq!(filter.addresses().count(), MAX_IR_ADDRESSES);
+
+        filter.remove(address(IrProtocol::Samsung, 1));
+        assert!(filter.add(extra));
+        assert!(filter.accepts(&key(IrProtocol::Samsung, extra.address)));
+    }
+
+    #[test]
+    fn foreign_remote_is_dropped_and_counted() {
+        let mut decoder = IrDecoder::new(IdlePin);
+        let stock = nec16(0xFF00, false);
+        assert_eq!(decoder.filter(stock), Some(stock));
+        assert_eq!(decoder.take_rejected(), None);
+
+        let foreign = nec16(0x1234, false);
+        assert_eq!(decoder.filter(foreign), None);
+        assert_eq!(decoder.filter(nec16(0x1234, true)), None);
+        assert_eq!(decoder.take_rejected(), Some((foreign.key, 2)));
+        // Reported once, repeats alone aren't reported again but are counted
+        assert_eq!(decoder.filter(nec16(0x1234, true)), None);
+        assert_eq!(decoder.take_rejected(), None);
+        assert_eq!(decoder.filter(foreign), None);
+        assert_eq!(decoder.take_rejected(), Some((foreign.key, 4)));
+
+        decoder.set_filter(IrAddressFilter::any());
+        assert_eq!(decoder.filter(foreign), Some(foreign));
+        assert_eq!(decoder.take_rejected(), None);
     }
 }
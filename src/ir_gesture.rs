@@ -0,0 +1,226 @@
+use crate::{Button, Duration, Instant, IrCommand};
+
+/// Held at least this long is a hold, reported on the next repeat frame
+pub const IR_HOLD_DURATION: Duration = Duration::from_millis(300);
+
+/// A key is released once no repeat frame has come for this long,
+/// NEC repeats every 108 ms, RC5 and RC6 about as often
+pub const IR_RELEASE_TIMEOUT: Duration = Duration::from_millis(200);
+
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub enum IrGesture {
+    Press(Button),
+    /// Once per press, when held for `IR_HOLD_DURATION`
+    Hold(Button),
+    /// Every repeat frame after the hold
+    HoldRepeat(Button),
+    Release(Button),
+}
+
+/// Classifies remote commands into gestures, driven by timestamps
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct IrGestureClassifier {
+    /// The key down, when it was pressed and whether the hold was reported
+    held: Option<(Button, Instant, bool)>,
+    last_frame_at: Instant,
+    /// A press that came while another key was still down, reported after its release
+    pending_press: Option<Button>,
+}
+
+impl IrGestureClassifier {
+    pub const fn new() -> Self {
+        IrGestureClassifier {
+            held: None,
+            last_frame_at: Instant::from_millis(0),
+            pending_press: None,
+        }
+    }
+
+    /// Call this with each command, and periodically with None to see releases
+    pub fn update(&mut self, cmd: Option<IrCommand>, now: Instant) -> Option<IrGesture> {
+        match cmd {
+            Some(cmd) if !cmd.repeat => {
+                self.last_frame_at = now;
+                match self.held.replace((cmd.button, now, false)) {
+                    Some((button, _, _)) => {
+                        self.pending_press = Some(cmd.button);
+                        Some(IrGesture::Release(button))
+                    }
+                    None => Some(IrGesture::Press(cmd.button)),
+                }
+            }
+            Some(cmd) => {
+                // A repeat without its press, e.g. the first frame was missed, is dropped
+                let (button, pressed_at, hold_reported) = match self.held.as_mut() {
+                    Some(held) if held.0 == cmd.button => held,
+                    _ => return None,
+                };
+                self.last_frame_at = now;
+                if *hold_reported {
+                    Some(IrGesture::HoldRepeat(*button))
+                } else if now.duration_since(*pressed_at) >= IR_HOLD_DURATION {
+                    *hold_reported = true;
+                    Some(IrGesture::Hold(*button))
+                } else {
+                    None
+                }
+            }
+            None => {
+                if let Some(button) = self.pending_press.take() {
+                    return Some(IrGesture::Press(button));
+                }
+                match self.held {
+                    Some((button, _, _))
+                        if now.duration_since(self.last_frame_at) >= IR_RELEASE_TIMEOUT =>
+                    {
+                        self.held = None;
+                        Some(IrGesture::Release(button))
+                    }
+                    _ => None,
+                }
+            }
+        }
+    }
+
+    /// Forgets the key down without reporting its release
+    pub fn reset(&mut self) {
+        *self = Self::new();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PRESS: IrCommand = IrCommand {
+        button: Button::BrightnessUp,
+        repeat: false,
+    };
+    const REPEAT: IrCommand = IrCommand {
+        button: Button::BrightnessUp,
+        repeat: true,
+    };
+    /// NEC's repeat frame interval
+    const REPEAT_INTERVAL: u32 = 108;
+
+    fn at(ms: u32) -> Instant {
+        Instant::from_millis(ms)
+    }
+
+    #[test]
+    fn press_hold_release() {
+        use IrGesture::*;
+        let button = PRESS.button;
+        let mut ir = IrGestureClassifier::new();
+        assert_eq!(ir.update(Some(PRESS), at(0)), Some(Press(button)));
+        assert_eq!(ir.update(None, at(50)), None);
+        // Repeats before the hold duration say nothing
+        assert_eq!(ir.update(Some(REPEAT), at(REPEAT_INTERVAL)), None);
+        assert_eq!(ir.update(Some(REPEAT), at(2 * REPEAT_INTERVAL)), None);
+        assert_eq!(
+            ir.update(Some(REPEAT), at(3 * REPEAT_INTERVAL)),
+            Some(Hold(button))
+        );
+        assert_eq!(
+            ir.update(Some(REPEAT), at(4 * REPEAT_INTERVAL)),
+            Some(HoldRepeat(button))
+        );
+        let last_frame = 5 * REPEAT_INTERVAL;
+        assert_eq!(
+            ir.update(Some(REPEAT), at(last_frame)),
+            Some(HoldRepeat(button))
+        );
+
+        let release = last_frame + IR_RELEASE_TIMEOUT.as_millis();
+        assert_eq!(ir.update(None, at(release - 1)), None);
+        assert_eq!(ir.update(None, at(release)), Some(Release(button)));
+        assert_eq!(ir.update(None, at(release + 1000)), None);
+    }
+
+    #[test]
+    fn hold_is_reported_at_the_hold_duration() {
+        let mut ir = IrGestureClassifier::new();
+        let hold = IR_HOLD_DURATION.as_millis();
+        ir.update(Some(PRESS), at(1000));
+        assert_eq!(ir.update(Some(REPEAT), at(1000 + hold - 1)), None);
+        assert_eq!(
+            ir.update(Some(REPEAT), at(1000 + hold)),
+            Some(IrGesture::Hold(PRESS.button))
+        );
+    }
+
+    #[test]
+    fn short_press_is_released() {
+        let mut ir = IrGestureClassifier::new();
+        let release = IR_RELEASE_TIMEOUT.as_millis();
+        ir.update(Some(PRESS), at(0));
+        assert_eq!(
+            ir.update(None, at(release)),
+            Some(IrGesture::Release(PRESS.button))
+        );
+    }
+
+    #[test]
+    fn repeat_without_a_press_is_dropped() {
+        let mut ir = IrGestureClassifier::new();
+        assert_eq!(ir.update(Some(REPEAT), at(0)), None);
+        assert_eq!(ir.update(Some(REPEAT), at(1000)), None);
+        assert_eq!(ir.update(None, at(2000)), None);
+
+        // Nor does a repeat of another key continue the one held
+        ir.update(Some(PRESS), at(3000));
+        let other = IrCommand {
+            button: Button::Red,
+            repeat: true,
+        };
+        assert_eq!(ir.update(Some(other), at(3400)), None);
+        assert_eq!(
+            ir.update(Some(REPEAT), at(3400)),
+            Some(IrGesture::Hold(PRESS.button))
+        );
+    }
+
+    #[test]
+    fn new_key_while_one_is_held() {
+        use IrGesture::*;
+        let mut ir = IrGestureClassifier::new();
+        let red = IrCommand {
+            button: Button::Red,
+            repeat: false,
+        };
+        ir.update(Some(PRESS), at(0));
+        ir.update(Some(REPEAT), at(REPEAT_INTERVAL));
+        // The held key is released first, the new press follows on the next update
+        assert_eq!(
+            ir.update(Some(red), at(2 * REPEAT_INTERVAL)),
+            Some(Release(PRESS.button))
+        );
+        assert_eq!(
+            ir.update(None, at(2 * REPEAT_INTERVAL + 5)),
+            Some(Press(Button::Red))
+        );
+
+        // And it's held from its own press
+        let hold = 2 * REPEAT_INTERVAL + IR_HOLD_DURATION.as_millis();
+        let red_repeat = IrCommand {
+            button: Button::Red,
+            repeat: true,
+        };
+        assert_eq!(ir.update(Some(red_repeat), at(hold - 1)), None);
+        assert_eq!(
+            ir.update(Some(red_repeat), at(hold)),
+            Some(Hold(Button::Red))
+        );
+        let release = hold + IR_RELEASE_TIMEOUT.as_millis();
+        assert_eq!(ir.update(None, at(release)), Some(Release(Button::Red)));
+    }
+
+    #[test]
+    fn reset_forgets_the_key() {
+        let mut ir = IrGestureClassifier::new();
+        ir.update(Some(PRESS), at(0));
+        ir.reset();
+        assert_eq!(ir.update(None, at(1000)), None);
+        assert_eq!(ir.update(Some(REPEAT), at(1100)), None);
+    }
+}
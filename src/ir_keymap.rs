@@ -0,0 +1,260 @@
+use crate::{Button, IrCommand, IrFrame, IrKey, IrProtocol};
+use log::debug;
+
+/// Number of buttons a keymap covers, see `Button::enumerate`
+pub const NUM_BUTTONS: usize = 24;
+
+/// Maps a remote's keys to buttons, learned with `IrLearner`.
+/// An empty keymap is the stock NEC remote
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct IrKeymap {
+    /// Indexed like `Button::enumerate`
+    keys: [Option<IrKey>; NUM_BUTTONS],
+}
+
+impl IrKeymap {
+    pub const fn new() -> Self {
+        IrKeymap {
+            keys: [None; NUM_BUTTONS],
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.iter().all(|k| k.is_none())
+    }
+
+    pub fn key(&self, button: Button) -> Option<IrKey> {
+        Self::index(button).and_then(|i| self.keys[i])
+    }
+
+    /// Ignored for Button::Unknown
+    pub fn set_key(&mut self, button: Button, key: Option<IrKey>) {
+        if let Some(i) = Self::index(button) {
+            self.keys[i] = key;
+        }
+    }
+
+    pub fn button(&self, key: IrKey) -> Button {
+        if self.is_empty() {
+            return match key.protocol {
+                IrProtocol::Nec | IrProtocol::Nec16 => Button::from_stock_command(key.command),
+                _ => Button::Unknown(key.command),
+            };
+        }
+        self.keys
+            .iter()
+            .position(|k| *k == Some(key))
+            .map(|i| Button::enumerate()[i])
+            .unwrap_or(Button::Unknown(key.command))
+    }
+
+    /// The keys learned
+    pub fn keys(&self) -> impl Iterator<Item = &IrKey> {
+        self.keys.iter().flatten()
+    }
+
+    pub fn command(&self, frame: IrFrame) -> IrCommand {
+        IrCommand {
+            button: self.button(frame.key),
+            repeat: frame.repeat,
+        }
+    }
+
+    fn index(button: Button) -> Option<usize> {
+        Button::enumerate().iter().position(|b| *b == button)
+    }
+}
+
+/// Walks through every button in the remote's layout, learning the key pressed for each
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct IrLearner {
+    keymap: IrKeymap,
+    index: usize,
+}
+
+impl IrLearner {
+    pub const fn new() -> Self {
+        IrLearner {
+            keymap: IrKeymap::new(),
+            index: 0,
+        }
+    }
+
+    /// The button waiting for a key, None once every button is done
+    pub fn button(&self) -> Option<Button> {
+        Button::enumerate().get(self.index).copied()
+    }
+
+    /// Repeats and keys already learned are ignored, returns true if the key was taken
+    pub fn learn(&mut self, frame: IrFrame) -> bool {
+        let button = match self.button() {
+            Some(b) if !frame.repeat => b,
+            _ => return false,
+        };
+        if self.keymap.keys.contains(&Some(frame.key)) {
+            debug!("Key {:?} already learned", frame.key);
+            return false;
+        }
+        debug!("Learned {} {:?}", button, frame.key);
+        self.keymap.set_key(button, Some(frame.key));
+        self.index += 1;
+        true
+    }
+
+    /// Leaves the button unmapped
+    pub fn skip(&mut self) {
+        if let Some(button) = self.button() {
+            debug!("Skipped {}", button);
+            self.index += 1;
+        }
+    }
+
+    pub fn keymap(&self) -> &IrKeymap {
+        &self.keymap
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A remote with its own address and commands, none of them the stock ones
+    fn key(i: usize) -> IrKey {
+        IrKey {
+            protocol: IrProtocol::Nec16,
+            address: 0x1234,
+            command: 0x80 + i as u8,
+        }
+    }
+
+    fn press(key: IrKey) -> IrFrame {
+        IrFrame { key, repeat: false }
+    }
+
+    #[test]
+    fn empty_keymap_is_the_stock_remote() {
+        let keymap = IrKeymap::new();
+        assert!(keymap.is_empty());
+        for (protocol, address) in [(IrProtocol::Nec, 0x00), (IrProtocol::Nec16, 0xABCD)] {
+            let key = |command| IrKey {
+                protocol,
+                address,
+                command,
+            };
+            assert_eq!(keymap.button(key(7)), Button::On);
+            assert_eq!(keymap.button(key(27)), Button::Fade);
+            assert_eq!(keymap.button(key(99)), Button::Unknown(99));
+        }
+        // The stock remote is NEC, the same command from another protocol is another key
+        for protocol in [IrProtocol::Samsung, IrProtocol::Rc5, IrProtocol::Rc6] {
+            let key = IrKey {
+                protocol,
+                address: 0,
+                command: 7,
+            };
+            assert_eq!(keymap.button(key), Button::Unknown(7));
+        }
+        let repeat = IrFrame {
+            key: IrKey {
+                protocol: IrProtocol::Nec,
+                address: 0,
+                command: 5,
+            },
+            repeat: true,
+        };
+        assert_eq!(
+            keymap.command(repeat),
+            IrCommand {
+                button: Button::BrightnessUp,
+                repeat: true
+            }
+        );
+    }
+
+    #[test]
+    fn learns_every_button_in_order() {
+        let mut learner = IrLearner::new();
+        for (i, button) in Button::enumerate().iter().enumerate() {
+            assert_eq!(learner.button(), Some(*button));
+            assert!(learner.learn(press(key(i))));
+        }
+        assert_eq!(learner.button(), None);
+        assert!(!learner.learn(press(key(NUM_BUTTONS))));
+
+        let keymap = learner.keymap();
+        for (i, button) in Button::enumerate().iter().enumerate() {
+            assert_eq!(keymap.key(*button), Some(key(i)));
+            assert_eq!(keymap.button(key(i)), *button);
+        }
+        // Once learned, the stock commands are just unknown keys
+        let stock_on = IrKey {
+            protocol: IrProtocol::Nec,
+            address: 0,
+            command: 7,
+        };
+        assert_eq!(keymap.button(stock_on), Button::Unknown(7));
+    }
+
+    #[test]
+    fn skipped_buttons_stay_unmapped() {
+        let mut learner = IrLearner::new();
+        learner.skip();
+        assert!(learner.learn(press(key(1))));
+        assert_eq!(learner.keymap().key(Button::BrightnessUp), None);
+        assert_eq!(learner.keymap().key(Button::BrightnessDown), Some(key(1)));
+        assert_eq!(
+            learner.keymap().button(key(0)),
+            Button::Unknown(key(0).command)
+        );
+
+        // Skipping past the end is a no-op
+        for _ in 0..2 * NUM_BUTTONS {
+            learner.skip();
+        }
+        assert_eq!(learner.button(), None);
+        assert_eq!(learner.keymap().key(Button::BrightnessDown), Some(key(1)));
+
+        // Skipping every button goes back to the stock remote
+        let mut learner = IrLearner::new();
+        for _ in 0..NUM_BUTTONS {
+            learner.skip();
+        }
+        assert!(learner.keymap().is_empty());
+        assert_eq!(
+            learner.keymap().button(key(0)),
+            Button::Unknown(key(0).command)
+        );
+        assert_eq!(
+            learner.keymap().button(IrKey {
+                protocol: IrProtocol::Nec,
+                address: 0,
+                command: 7
+            }),
+            Button::On
+        );
+    }
+
+    #[test]
+    fn repeats_and_duplicate_keys_are_ignored() {
+        let mut learner = IrLearner::new();
+        assert!(learner.learn(press(key(0))));
+        assert!(!learner.learn(IrFrame {
+            key: key(1),
+            repeat: true,
+        }));
+        // The same key again, the button keeps waiting
+        assert!(!learner.learn(press(key(0))));
+        assert_eq!(learner.button(), Some(Button::BrightnessDown));
+        assert!(learner.learn(press(key(1))));
+        assert_eq!(learner.keymap().key(Button::BrightnessUp), Some(key(0)));
+        assert_eq!(learner.keymap().key(Button::BrightnessDown), Some(key(1)));
+    }
+
+    #[test]
+    fn unknown_button_isnt_mapped() {
+        let mut keymap = IrKeymap::new();
+        keymap.set_key(Button::Unknown(3), Some(key(0)));
+        assert!(keymap.is_empty());
+        assert_eq!(keymap.key(Button::Unknown(3)), None);
+    }
+}
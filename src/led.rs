@@ -1,6 +1,6 @@
-use crate::Button;
+use crate::{Button, Duration};
 use colorous::RAINBOW as PALETTE;
-use core::{cmp::Ordering, fmt, iter};
+use core::{fmt, iter, ops::Range};
 use embedded_hal::spi::FullDuplex;
 use log::error;
 use smart_leds::SmartLedsWrite;
@@ -17,6 +17,14 @@ pub const COLOR_OFF: RGBW8 = RGBW {
     a: White(0),
 };
 
+/// Largest number of pixels a driver can have, the 12 pixel ring
+pub const MAX_NUM_LEDS: usize = 12;
+
+/// A per-pixel frame, only the first `InfallibleLedDriver::NUM_LEDS` are written
+pub type Frame = [RGBW8; MAX_NUM_LEDS];
+
+pub const FRAME_OFF: Frame = [COLOR_OFF; MAX_NUM_LEDS];
+
 #[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
 pub enum BasicColor {
     Red,
@@ -128,12 +136,35 @@ impl RandomColorGen {
         let index = self.0.rand_range(0..colors.len() as u32) as usize;
         colors[index]
     }
+
+    pub fn rand_range(&mut self, range: Range<u32>) -> u32 {
+        self.0.rand_range(range)
+    }
+
+    /// A random color other than `current_color`
+    pub fn next_rand_rgb(&mut self, current_color: RGBW8) -> RGBW8 {
+        loop {
+            let next = self.rand_rgb();
+            if next != current_color {
+                break next;
+            }
+        }
+    }
+
+    /// A random basic color other than `current_color`
+    pub fn next_rand_color(&mut self, current_color: RGBW8) -> BasicColor {
+        loop {
+            let next = self.rand_color();
+            if next.as_rgbw() != current_color {
+                break next;
+            }
+        }
+    }
 }
 
 pub trait FadeOffRgbw {
     fn set_off(&mut self);
     fn is_off(&self) -> bool;
-    fn step_down(&mut self);
 }
 
 impl FadeOffRgbw for RGBW8 {
@@ -147,19 +178,42 @@ impl FadeOffRgbw for RGBW8 {
     fn is_off(&self) -> bool {
         self.r == 0 && self.g == 0 && self.b == 0 && self.a.0 == 0
     }
+}
 
-    fn step_down(&mut self) {
-        self.r = self.r.saturating_sub(1);
-        self.g = self.g.saturating_sub(1);
-        self.b = self.b.saturating_sub(1);
-        self.a.0 = self.a.0.saturating_sub(1);
+/// Color space a fade travels through
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub enum Interpolation {
+    /// Straight line in RGB, can pass through desaturated colors
+    Rgb,
+    /// Hue takes the shortest arc around the color wheel, stays saturated
+    Hsv,
+}
+
+impl Interpolation {
+    pub fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(Interpolation::Rgb),
+            1 => Some(Interpolation::Hsv),
+            _ => None,
+        }
     }
 }
 
+impl From<Interpolation> for u8 {
+    fn from(i: Interpolation) -> Self {
+        i as u8
+    }
+}
+
+/// Fades interpolate the perceptual color, see `Lightness`
 pub trait FadeToRgbw {
     fn destination_reached(&self, destination: &RGBW8) -> bool;
 
-    fn step_to(&mut self, destination: &RGBW8);
+    /// Proportional interpolation, all channels arrive at the destination together
+    fn interpolate(&self, destination: &RGBW8, elapsed: Duration, duration: Duration) -> RGBW8;
+
+    /// Like `interpolate`, but the RGB part travels through HSV, white is linear
+    fn interpolate_hsv(&self, destination: &RGBW8, elapsed: Duration, duration: Duration) -> RGBW8;
 }
 
 impl FadeToRgbw for RGBW8 {
@@ -167,46 +221,170 @@ impl FadeToRgbw for RGBW8 {
         self == destination
     }
 
-    fn step_to(&mut self, destination: &RGBW8) {
-        match self.r.cmp(&destination.r) {
-            Ordering::Greater => self.r = self.r.saturating_sub(1),
-            Ordering::Less => self.r = self.r.saturating_add(1),
-            Ordering::Equal => (),
+    fn interpolate(&self, destination: &RGBW8, elapsed: Duration, duration: Duration) -> RGBW8 {
+        if elapsed >= duration {
+            return *destination;
+        }
+        let elapsed = elapsed.as_millis() as i64;
+        let duration = duration.as_millis() as i64;
+        let lerp = |from: u8, to: u8| {
+            let delta = to as i64 - from as i64;
+            (from as i64 + (delta * elapsed / duration)) as u8
+        };
+        RGBW {
+            r: lerp(self.r, destination.r),
+            g: lerp(self.g, destination.g),
+            b: lerp(self.b, destination.b),
+            a: White(lerp(self.a.0, destination.a.0)),
+        }
+    }
+
+    fn interpolate_hsv(&self, destination: &RGBW8, elapsed: Duration, duration: Duration) -> RGBW8 {
+        if elapsed >= duration {
+            return *destination;
+        } else if elapsed == Duration::ZERO {
+            return *self;
         }
-        match self.g.cmp(&destination.g) {
-            Ordering::Greater => self.g = self.g.saturating_sub(1),
-            Ordering::Less => self.g = self.g.saturating_add(1),
-            Ordering::Equal => (),
+        let elapsed = elapsed.as_millis() as i64;
+        let duration = duration.as_millis() as i64;
+        let lerp = |from: i64, to: i64| from + ((to - from) * elapsed / duration);
+
+        let mut from = Hsv16::from_rgb(self.r, self.g, self.b);
+        let mut to = Hsv16::from_rgb(destination.r, destination.g, destination.b);
+
+        // Hue is meaningless for grey/black, don't sweep through the wheel.
+        // Saturation is meaningless for black too, don't wash out a fade from/to off
+        if from.is_achromatic() {
+            from.hue = to.hue;
+            if from.val == 0 {
+                from.sat = to.sat;
+            }
+        } else if to.is_achromatic() {
+            to.hue = from.hue;
+            if to.val == 0 {
+                to.sat = from.sat;
+            }
         }
-        match self.b.cmp(&destination.b) {
-            Ordering::Greater => self.b = self.b.saturating_sub(1),
-            Ordering::Less => self.b = self.b.saturating_add(1),
-            Ordering::Equal => (),
+
+        let mut hue_delta = to.hue as i64 - from.hue as i64;
+        if hue_delta > HUE_MAX / 2 {
+            hue_delta -= HUE_MAX;
+        } else if hue_delta < -HUE_MAX / 2 {
+            hue_delta += HUE_MAX;
+        }
+        let hue = (from.hue as i64 + (hue_delta * elapsed / duration)).rem_euclid(HUE_MAX);
+
+        let (r, g, b) = Hsv16 {
+            hue: hue as u16,
+            sat: lerp(from.sat as i64, to.sat as i64) as u8,
+            val: lerp(from.val as i64, to.val as i64) as u8,
+        }
+        .to_rgb();
+        RGBW {
+            r,
+            g,
+            b,
+            a: White(lerp(self.a.0 as i64, destination.a.0 as i64) as u8),
+        }
+    }
+}
+
+/// Hue resolution, 255 steps per sector of the color wheel
+const HUE_SECTOR: i64 = 255;
+const HUE_MAX: i64 = 6 * HUE_SECTOR;
+
+/// HSV with a finer hue than `smart_leds::hsv::Hsv`, its 8-bit hue steps visibly in slow fades
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+struct Hsv16 {
+    hue: u16,
+    sat: u8,
+    val: u8,
+}
+
+impl Hsv16 {
+    fn from_rgb(r: u8, g: u8, b: u8) -> Self {
+        let (r, g, b) = (r as i64, g as i64, b as i64);
+        let max = r.max(g).max(b);
+        let min = r.min(g).min(b);
+        let delta = max - min;
+        let hue = if delta == 0 {
+            0
+        } else if max == r {
+            ((g - b) * HUE_SECTOR / delta).rem_euclid(HUE_MAX)
+        } else if max == g {
+            (2 * HUE_SECTOR) + ((b - r) * HUE_SECTOR / delta)
+        } else {
+            (4 * HUE_SECTOR) + ((r - g) * HUE_SECTOR / delta)
+        };
+        Hsv16 {
+            hue: hue as u16,
+            sat: if max == 0 {
+                0
+            } else {
+                (delta * 255 / max) as u8
+            },
+            val: max as u8,
         }
-        match self.a.0.cmp(&destination.a.0) {
-            Ordering::Greater => self.a.0 = self.a.0.saturating_sub(1),
-            Ordering::Less => self.a.0 = self.a.0.saturating_add(1),
-            Ordering::Equal => (),
+    }
+
+    fn to_rgb(self) -> (u8, u8, u8) {
+        let (v, s) = (self.val as i64, self.sat as i64);
+        let sector = self.hue as i64 / HUE_SECTOR;
+        let f = self.hue as i64 % HUE_SECTOR;
+        let p = (v * (255 - s) / 255) as u8;
+        let q = (v * (255 - (s * f / HUE_SECTOR)) / 255) as u8;
+        let t = (v * (255 - (s * (HUE_SECTOR - f) / HUE_SECTOR)) / 255) as u8;
+        let v = v as u8;
+        match sector {
+            0 => (v, t, p),
+            1 => (q, v, p),
+            2 => (p, v, t),
+            3 => (p, q, v),
+            4 => (t, p, v),
+            _ => (v, p, q),
         }
     }
+
+    fn is_achromatic(&self) -> bool {
+        self.sat == 0 || self.val == 0
+    }
 }
 
-// TODO - add brightness later
 pub trait InfallibleLedDriver {
     const NUM_LEDS: usize;
 
-    fn set_pixels(&mut self, color: &RGBW8);
+    /// Global brightness applied to every pixel written, 255 is full scale
+    fn set_brightness(&mut self, brightness: u8);
+
+    /// Write one color per pixel, at most `NUM_LEDS` are used
+    fn write_pixels<I>(&mut self, pixels: I)
+    where
+        I: Iterator<Item = RGBW8>;
+
+    fn set_frame(&mut self, frame: &[RGBW8]) {
+        self.write_pixels(frame.iter().cloned());
+    }
+
+    fn set_pixels(&mut self, color: &RGBW8) {
+        self.write_pixels(iter::repeat_n(*color, Self::NUM_LEDS));
+    }
 
     fn set_off(&mut self) {
         self.set_pixels(&COLOR_OFF);
     }
 }
 
-pub struct InfallibleSk6812w<SPI>(Ws2812<SPI, Sk6812w>);
+pub struct InfallibleSk6812w<SPI> {
+    drv: Ws2812<SPI, Sk6812w>,
+    brightness: u8,
+}
 
 impl<SPI> From<Ws2812<SPI, Sk6812w>> for InfallibleSk6812w<SPI> {
-    fn from(t: Ws2812<SPI, Sk6812w>) -> Self {
-        InfallibleSk6812w(t)
+    fn from(drv: Ws2812<SPI, Sk6812w>) -> Self {
+        InfallibleSk6812w {
+            drv,
+            brightness: u8::MAX,
+        }
     }
 }
 
@@ -215,28 +393,32 @@ where
     SPI: FullDuplex<u8, Error = E>,
     E: fmt::Debug,
 {
-    // TODO testing on the 8 pixel strip, the ring has 12
-    //const NUM_LEDS: usize = 8;
-    const NUM_LEDS: usize = 1;
+    const NUM_LEDS: usize = MAX_NUM_LEDS;
 
-    fn set_pixels(&mut self, color: &RGBW8) {
-        let pixels = iter::repeat(color).take(Self::NUM_LEDS);
+    fn set_brightness(&mut self, brightness: u8) {
+        self.brightness = brightness;
+    }
+
+    fn write_pixels<I>(&mut self, pixels: I)
+    where
+        I: Iterator<Item = RGBW8>,
+    {
+        let pixels = pixels.take(Self::NUM_LEDS);
 
         // Unwrap/panic ok, will trigger watchdog reset
-        self.0
-            .write(pixels.cloned())
+        self.drv
+            .write(lightness_iter(brightness_iter(pixels, self.brightness)))
             .map_err(|e| error!("Failed to set pixels {:?}", e))
             .unwrap();
     }
 }
 
-/*
-struct Brightness<I> {
+pub struct Brightness<I> {
     iter: I,
     brightness: u8,
 }
 
-impl<'a, I> Iterator for Brightness<I>
+impl<I> Iterator for Brightness<I>
 where
     I: Iterator<Item = RGBW<u8>>,
 {
@@ -252,10 +434,318 @@ where
     }
 }
 
-fn brightness_iter<I>(iter: I, brightness: u8) -> Brightness<I>
+pub fn brightness_iter<I>(iter: I, brightness: u8) -> Brightness<I>
 where
     I: Iterator<Item = RGBW<u8>>,
 {
     Brightness { iter, brightness }
 }
-*/
+
+/// Maps perceptual lightness (CIE L*, scaled to 0..=255) to linear LED output
+#[rustfmt::skip]
+const CIE_LIGHTNESS: [u8; 256] = [
+    0, 0, 0, 0, 0, 1, 1, 1, 1, 1, 1, 1, 1, 1, 2, 2, 2, 2, 2, 2, 2, 2, 2, 3, 3, 3, 3, 3,
+    3, 3, 3, 4, 4, 4, 4, 4, 4, 5, 5, 5, 5, 5, 6, 6, 6, 6, 6, 7, 7, 7, 7, 8, 8, 8, 8, 9,
+    9, 9, 10, 10, 10, 10, 11, 11, 11, 12, 12, 12, 13, 13, 13, 14, 14, 15, 15, 15, 16,
+    16, 17, 17, 17, 18, 18, 19, 19, 20, 20, 21, 21, 22, 22, 23, 23, 24, 24, 25, 25, 26,
+    26, 27, 28, 28, 29, 29, 30, 31, 31, 32, 32, 33, 34, 34, 35, 36, 37, 37, 38, 39, 39,
+    40, 41, 42, 43, 43, 44, 45, 46, 47, 47, 48, 49, 50, 51, 52, 53, 54, 54, 55, 56, 57,
+    58, 59, 60, 61, 62, 63, 64, 65, 66, 67, 68, 70, 71, 72, 73, 74, 75, 76, 77, 79, 80,
+    81, 82, 83, 85, 86, 87, 88, 90, 91, 92, 94, 95, 96, 98, 99, 100, 102, 103, 105, 106,
+    108, 109, 110, 112, 113, 115, 116, 118, 120, 121, 123, 124, 126, 128, 129, 131, 132,
+    134, 136, 138, 139, 141, 143, 145, 146, 148, 150, 152, 154, 155, 157, 159, 161, 163,
+    165, 167, 169, 171, 173, 175, 177, 179, 181, 183, 185, 187, 189, 191, 193, 196, 198,
+    200, 202, 204, 207, 209, 211, 214, 216, 218, 220, 223, 225, 228, 230, 232, 235, 237,
+    240, 242, 245, 247, 250, 252, 255,
+];
+
+/// Lightness correction, the last stage before the driver output.
+/// Colors and fades upstream are in perceptual space, so a linear fade or brightness step
+/// in code looks even on the LEDs.
+pub struct Lightness<I> {
+    iter: I,
+}
+
+impl<I> Iterator for Lightness<I>
+where
+    I: Iterator<Item = RGBW<u8>>,
+{
+    type Item = RGBW<u8>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.next().map(|p| RGBW {
+            r: CIE_LIGHTNESS[p.r as usize],
+            g: CIE_LIGHTNESS[p.g as usize],
+            b: CIE_LIGHTNESS[p.b as usize],
+            a: White(CIE_LIGHTNESS[p.a.0 as usize]),
+        })
+    }
+}
+
+pub fn lightness_iter<I>(iter: I) -> Lightness<I>
+where
+    I: Iterator<Item = RGBW<u8>>,
+{
+    Lightness { iter }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 4 pixel strip keeping what was written last
+    #[derive(Default)]
+    struct Strip {
+        pixels: [RGBW8; 4],
+        written: usize,
+    }
+
+    impl InfallibleLedDriver for Strip {
+        const NUM_LEDS: usize = 4;
+
+        fn set_brightness(&mut self, _brightness: u8) {}
+
+        fn write_pixels<I>(&mut self, pixels: I)
+        where
+            I: Iterator<Item = RGBW8>,
+        {
+            self.written = 0;
+            for (p, c) in self.pixels.iter_mut().zip(pixels.take(Self::NUM_LEDS)) {
+                *p = c;
+                self.written += 1;
+            }
+        }
+    }
+
+    const RED: RGBW8 = RGBW {
+        r: 255,
+        g: 0,
+        b: 0,
+        a: White(0),
+    };
+
+    #[test]
+    fn set_pixels_fills_every_pixel() {
+        let mut strip = Strip::default();
+        strip.set_pixels(&RED);
+        assert_eq!(strip.pixels, [RED; 4]);
+        assert_eq!(strip.written, 4);
+        strip.set_off();
+        assert_eq!(strip.pixels, [COLOR_OFF; 4]);
+    }
+
+    #[test]
+    fn set_frame_writes_per_pixel() {
+        let mut strip = Strip::default();
+        let mut frame = FRAME_OFF;
+        frame[1] = RED;
+        frame[MAX_NUM_LEDS - 1] = RED;
+        strip.set_frame(&frame);
+        assert_eq!(strip.pixels, [COLOR_OFF, RED, COLOR_OFF, COLOR_OFF]);
+        assert_eq!(strip.written, 4);
+
+        // A short frame leaves the rest alone
+        strip.set_frame(&[RED]);
+        assert_eq!(strip.pixels, [RED, RED, COLOR_OFF, COLOR_OFF]);
+        assert_eq!(strip.written, 1);
+    }
+
+    fn scaled(color: RGBW8, brightness: u8) -> RGBW8 {
+        brightness_iter(iter::once(color), brightness)
+            .next()
+            .unwrap()
+    }
+
+    #[test]
+    fn full_brightness_is_unchanged() {
+        for color in [
+            RGBW8::new_alpha(255, 128, 1, White(0)),
+            RGBW8::new_alpha(0, 0, 0, White(255)),
+        ] {
+            assert_eq!(scaled(color, u8::MAX), color);
+        }
+    }
+
+    #[test]
+    fn brightness_scales_every_channel() {
+        let color = RGBW8::new_alpha(255, 128, 64, White(200));
+        assert_eq!(
+            scaled(color, 127),
+            RGBW8::new_alpha(127, 64, 32, White(100))
+        );
+        assert_eq!(scaled(color, 63), RGBW8::new_alpha(63, 32, 16, White(50)));
+        assert_eq!(scaled(color, 0), RGBW8::new_alpha(0, 0, 0, White(0)));
+    }
+
+    #[test]
+    fn brightness_applies_to_each_pixel() {
+        let pixels = [
+            RGBW8::new_alpha(255, 0, 0, White(0)),
+            RGBW8::new_alpha(0, 255, 0, White(0)),
+        ];
+        let mut scaled = brightness_iter(pixels.iter().copied(), 15);
+        assert_eq!(scaled.next(), Some(RGBW8::new_alpha(15, 0, 0, White(0))));
+        assert_eq!(scaled.next(), Some(RGBW8::new_alpha(0, 15, 0, White(0))));
+        assert_eq!(scaled.next(), None);
+    }
+
+    /// CIE 1931 lightness to relative luminance, rounded to 0..=255
+    fn cie_formula(value: u8) -> u8 {
+        let l = f64::from(value) * 100.0 / 255.0;
+        let y = if l <= 8.0 {
+            l / 903.3
+        } else {
+            let t = (l + 16.0) / 116.0;
+            t * t * t
+        };
+        ((y * 255.0) + 0.5) as u8
+    }
+
+    #[test]
+    fn lightness_keeps_the_endpoints() {
+        assert_eq!(CIE_LIGHTNESS[0], 0);
+        assert_eq!(CIE_LIGHTNESS[255], 255);
+    }
+
+    #[test]
+    fn lightness_is_monotonic() {
+        assert!(CIE_LIGHTNESS.windows(2).all(|w| w[0] <= w[1]));
+    }
+
+    #[test]
+    fn lightness_matches_the_cie_formula() {
+        for value in 0..=u8::MAX {
+            assert_eq!(
+                CIE_LIGHTNESS[value as usize],
+                cie_formula(value),
+                "{}",
+                value
+            );
+        }
+    }
+
+    #[test]
+    fn lightness_applies_to_every_channel() {
+        let pixels = [RGBW8::new_alpha(0, 64, 128, White(255))];
+        let mut corrected = lightness_iter(pixels.iter().copied());
+        assert_eq!(
+            corrected.next(),
+            Some(RGBW8::new_alpha(
+                0,
+                CIE_LIGHTNESS[64],
+                CIE_LIGHTNESS[128],
+                White(255)
+            ))
+        );
+        assert_eq!(corrected.next(), None);
+    }
+
+    #[test]
+    fn interpolation_reaches_every_channel_together() {
+        let from = RGBW8::new_alpha(0, 200, 10, White(255));
+        let to = RGBW8::new_alpha(255, 0, 20, White(250));
+        let duration = Duration::from_millis(1000);
+        for elapsed in (0..1000).step_by(10) {
+            let color = from.interpolate(&to, Duration::from_millis(elapsed), duration);
+            assert!(!color.destination_reached(&to), "{}", elapsed);
+            assert_ne!(color.r, to.r);
+            assert_ne!(color.g, to.g);
+        }
+        assert_eq!(from.interpolate(&to, duration, duration), to);
+        assert_eq!(
+            from.interpolate(&to, Duration::from_millis(2000), duration),
+            to
+        );
+    }
+
+    #[test]
+    fn interpolation_is_proportional() {
+        let from = RGBW8::new_alpha(0, 200, 10, White(255));
+        let to = RGBW8::new_alpha(255, 0, 20, White(55));
+        let duration = Duration::from_millis(1000);
+        assert_eq!(from.interpolate(&to, Duration::ZERO, duration), from);
+        assert_eq!(
+            from.interpolate(&to, Duration::from_millis(500), duration),
+            RGBW8::new_alpha(127, 100, 15, White(155))
+        );
+        assert_eq!(
+            from.interpolate(&to, Duration::from_millis(250), duration),
+            RGBW8::new_alpha(63, 150, 12, White(205))
+        );
+    }
+
+    fn hsv_steps(from: RGBW8, to: RGBW8) -> impl Iterator<Item = RGBW8> {
+        let duration = Duration::from_millis(100);
+        (0..=100).map(move |t| from.interpolate_hsv(&to, Duration::from_millis(t), duration))
+    }
+
+    #[test]
+    fn hsv_keeps_the_endpoints() {
+        let from = RGBW8::new_alpha(255, 0, 0, White(10));
+        let to = RGBW8::new_alpha(0, 0, 255, White(200));
+        let duration = Duration::from_millis(100);
+        assert_eq!(from.interpolate_hsv(&to, Duration::ZERO, duration), from);
+        assert_eq!(from.interpolate_hsv(&to, duration, duration), to);
+        assert_eq!(
+            from.interpolate_hsv(&to, Duration::from_millis(101), duration),
+            to
+        );
+    }
+
+    #[test]
+    fn hsv_red_to_blue_goes_through_magenta() {
+        let red = RGBW8::new_alpha(255, 0, 0, White(0));
+        let blue = RGBW8::new_alpha(0, 0, 255, White(0));
+        assert_eq!(
+            red.interpolate_hsv(&blue, Duration::from_millis(50), Duration::from_millis(100)),
+            RGBW8::new_alpha(255, 0, 255, White(0))
+        );
+        // The short way round, never through green
+        assert!(hsv_steps(red, blue).all(|c| c.g == 0 && (c.r == 255 || c.b == 255)));
+    }
+
+    #[test]
+    fn hsv_takes_the_shortest_arc_across_the_wrap() {
+        // Either side of red, the short arc crosses hue 0
+        let from = RGBW8::new_alpha(255, 0, 30, White(0));
+        let to = RGBW8::new_alpha(255, 30, 0, White(0));
+        assert_eq!(
+            from.interpolate_hsv(&to, Duration::from_millis(50), Duration::from_millis(100)),
+            RGBW8::new_alpha(255, 0, 0, White(0))
+        );
+        assert!(hsv_steps(from, to).all(|c| c.r == 255 && c.g <= 30 && c.b <= 30));
+    }
+
+    #[test]
+    fn hsv_from_grey_keeps_the_destination_hue() {
+        let grey = RGBW8::new_alpha(128, 128, 128, White(0));
+        let blue = RGBW8::new_alpha(0, 0, 255, White(0));
+        assert!(hsv_steps(grey, blue).all(|c| c.r == c.g && c.b >= c.r));
+    }
+
+    #[test]
+    fn hsv_from_black_keeps_the_destination_hue() {
+        let black = RGBW8::new_alpha(0, 0, 0, White(0));
+        let red = RGBW8::new_alpha(255, 0, 0, White(0));
+        // Stays saturated rather than fading through pink
+        assert!(hsv_steps(black, red).all(|c| c.g == 0 && c.b == 0));
+        assert!(hsv_steps(red, black).all(|c| c.g == 0 && c.b == 0));
+    }
+
+    #[test]
+    fn hsv16_round_trips_saturated_colors() {
+        let colors = [
+            (255, 0, 0),
+            (255, 255, 0),
+            (0, 255, 0),
+            (0, 255, 255),
+            (0, 0, 255),
+            (255, 0, 255),
+            (0, 0, 0),
+        ];
+        for (r, g, b) in colors.iter() {
+            assert_eq!(Hsv16::from_rgb(*r, *g, *b).to_rgb(), (*r, *g, *b));
+        }
+        assert_eq!(Hsv16::from_rgb(0, 0, 255).hue, 4 * HUE_SECTOR as u16);
+    }
+}
@@ -2,14 +2,44 @@
 
 pub extern crate stm32f3xx_hal as hal;
 
+mod config;
+mod console;
 mod controller;
+mod debounce;
+mod effect;
+mod flash;
 mod ir;
+mod ir_gesture;
+mod ir_keymap;
 mod led;
 mod logger;
+mod push_button;
+mod rtc;
+mod schedule;
+mod settings;
+mod speed;
+mod sunrise;
 mod system_clock;
+mod time_of_day;
+mod vibration;
 
+pub use config::*;
+pub use console::*;
 pub use controller::*;
+pub use debounce::*;
+pub use effect::*;
+pub use flash::*;
 pub use ir::*;
+pub use ir_gesture::*;
+pub use ir_keymap::*;
 pub use led::*;
 pub use logger::*;
+pub use push_button::*;
+pub use rtc::*;
+pub use schedule::*;
+pub use settings::*;
+pub use speed::*;
+pub use sunrise::*;
 pub use system_clock::*;
+pub use time_of_day::*;
+pub use vibration::*;
@@ -9,12 +9,11 @@ use hal::{
     gpio::{gpioa::PA15, Floating, Input},
     interrupt, pac,
     prelude::*,
-    serial::{Serial, Tx},
+    serial::{self, Rx, Serial, Tx},
     spi::Spi,
     timer::{self, Timer},
     watchdog::IndependentWatchDog,
 };
-use infrared::PeriodicReceiver;
 use log::{info, warn};
 use night_light_lib::*;
 use ws2812_spi::Ws2812;
@@ -25,8 +24,11 @@ static SYS_CLOCK: SystemClock = SystemClock::new();
 
 type IrRecvrPin = PA15<Input<Floating>>;
 static mut IR_TIMER: Option<Timer<pac::TIM2>> = None;
-static mut IR_RECVR: Option<IrReceiver<IrRecvrPin>> = None;
-static mut IR_CMD_QUEUE: IrCommandQueue = IrCommandQueue::new();
+static mut IR_DECODER: Option<IrDecoder<IrRecvrPin>> = None;
+static mut IR_FRAME_QUEUE: IrFrameQueue = IrFrameQueue::new();
+
+static mut CONSOLE_RX: Option<Rx<pac::USART1>> = None;
+static mut CONSOLE_QUEUE: ConsoleQueue = ConsoleQueue::new();
 
 #[entry]
 fn main() -> ! {
@@ -63,11 +65,11 @@ fn main() -> ! {
     // LED on, active low
     led.set_low().ok();
 
-    // Setup USART1 for the logger impl
+    // Setup USART1 for the logger impl and the console
     let uart_tx = gpiob.pb6.into_af7(&mut gpiob.moder, &mut gpiob.afrl);
     let uart_rx = gpiob.pb7.into_af7(&mut gpiob.moder, &mut gpiob.afrl);
 
-    let serial = Serial::usart1(
+    let mut serial = Serial::usart1(
         dp.USART1,
         (uart_tx, uart_rx),
         115_200.bps(),
@@ -75,11 +77,13 @@ fn main() -> ! {
         &mut rcc.apb2,
     );
 
-    // Construct a log impl over the transmitter
-    let (tx, _rx) = serial.split();
+    // Construct a log impl over the transmitter, the receiver feeds the console
+    serial.listen(serial::Event::Rxne);
+    let (tx, rx) = serial.split();
     unsafe {
         GLOBAL_LOGGER.set_inner(tx);
         log::set_logger(&GLOBAL_LOGGER).unwrap();
+        CONSOLE_RX.replace(rx);
     }
     log::set_max_level(log::LevelFilter::Trace);
 
@@ -107,23 +111,62 @@ fn main() -> ! {
         .into_floating_input(&mut gpioa.moder, &mut gpioa.pupdr);
     let mut ir_timer = Timer::tim2(dp.TIM2, IR_SAMPLE_RATE, clocks, &mut rcc.apb1);
     ir_timer.listen(timer::Event::Update);
-    let ir_recvr = PeriodicReceiver::new(ir_pin, IR_SAMPLE_RATE.0);
+    let ir_decoder = IrDecoder::new(ir_pin);
 
     unsafe {
         IR_TIMER.replace(ir_timer);
-        IR_RECVR.replace(ir_recvr);
+        IR_DECODER.replace(ir_decoder);
     }
 
-    let _vib_pin = gpioa
+    let vib_pin = gpioa
         .pa11
         .into_pull_up_input(&mut gpioa.moder, &mut gpioa.pupdr);
+    let mut vib_sensor = VibrationSensor::new(vib_pin, SYS_CLOCK.now());
+
+    let btn_pin = gpioa
+        .pa12
+        .into_pull_up_input(&mut gpioa.moder, &mut gpioa.pupdr);
+    let mut push_button = PushButton::new(btn_pin, SYS_CLOCK.now());
+
+    let mut controller = Controller::new(led_driver, &SYS_CLOCK, ControllerConfig::default())
+        .expect("Invalid controller config");
+
+    // Unsafe ok, the hal only uses the FLASH ACR register
+    let mut settings_store = SettingsStore::new(unsafe { InternalFlash::new() });
+    if let Some(settings) = settings_store.load() {
+        info!("Restoring settings {:?}", settings);
+        controller
+            .restore_settings(&settings)
+            .map_err(|e| warn!("Failed to restore settings {:?}", e))
+            .ok();
+    }
+
+    // Unsafe ok, the hal doesn't use the backup domain
+    let mut rtc = unsafe { InternalRtc::new(dp.RTC, dp.PWR, &SYS_CLOCK, || iwdg.feed()) };
+    match rtc.time_of_day() {
+        Some(time) => info!("Time of day {}", time),
+        None => warn!("RTC time not set"),
+    }
+
+    // Holding the button at power up starts the IR learning mode,
+    // its release isn't seen as a gesture
+    if push_button.is_pressed() {
+        info!("IR learning mode");
+        controller.start_ir_learning();
+        while push_button.is_pressed() {
+            iwdg.feed();
+        }
+    }
 
-    let mut controller = Controller::new(led_driver, &SYS_CLOCK);
     let mut controller_update_timer = Timer::tim4(dp.TIM4, 200.hz(), clocks, &mut rcc.apb1);
 
+    let mut console = Console::new();
+
     pac::NVIC::unpend(interrupt::TIM2);
+    pac::NVIC::unpend(interrupt::USART1_EXTI25);
     unsafe {
         pac::NVIC::unmask(interrupt::TIM2);
+        pac::NVIC::unmask(interrupt::USART1_EXTI25);
     };
 
     info!("Night light initialized");
@@ -131,20 +174,58 @@ fn main() -> ! {
     loop {
         iwdg.feed();
 
-        if let Some(cmd) = unsafe { IR_CMD_QUEUE.dequeue() } {
+        if let Some(frame) = unsafe { IR_FRAME_QUEUE.dequeue() } {
             led.toggle().ok();
-            controller.handle_ir_command(cmd);
+            controller.handle_ir_frame(frame);
+        }
+
+        while let Some(byte) = unsafe { CONSOLE_QUEUE.dequeue() } {
+            match console.push(byte) {
+                Some(Ok(ConsoleCommand::Time(time))) => {
+                    info!("Time of day {}", time);
+                    rtc.set_time_of_day(time)
+                        .map_err(|e| warn!("Failed to set the time of day {:?}", e))
+                        .ok();
+                }
+                Some(Ok(ConsoleCommand::LogLevel(level))) => log::set_max_level(level),
+                Some(Ok(ConsoleCommand::Help)) => {
+                    CONSOLE_USAGE.iter().for_each(|usage| info!("{}", usage));
+                    info!("Effects {:?}", EffectId::enumerate());
+                }
+                Some(Ok(cmd)) => controller
+                    .handle_console_command(cmd)
+                    .unwrap_or_else(|e| warn!("Console {:?} {:?}", cmd, e)),
+                Some(Err(e)) => warn!("Console {:?}", e),
+                None => (),
+            }
         }
 
         if controller_update_timer.wait().is_ok() {
+            if vib_sensor.poll(SYS_CLOCK.now()) {
+                controller.handle_auto_on_event();
+            }
+            if let Some(gesture) = push_button.poll(SYS_CLOCK.now()) {
+                controller.handle_button_gesture(gesture);
+            }
+            if let Some(time) = rtc.time_of_day() {
+                controller.handle_time_of_day(time);
+            }
             controller.update();
-        }
 
-        if SYS_CLOCK.is_near_wrap_around() && controller.is_idle() {
-            warn!("System clock is near the wrap around, resetting");
-            loop {
-                asm::nop();
+            // Unsafe ok, TIM2 can't preempt the critical section
+            let rejected = cortex_m::interrupt::free(|_| {
+                let decoder = unsafe { IR_DECODER.as_mut().unwrap() };
+                decoder.set_filter(controller.ir_filter());
+                decoder.take_rejected()
+            });
+            if let Some((key, count)) = rejected {
+                info!(
+                    "Rejected IR key {:?}, {} frames rejected so far",
+                    key, count
+                );
             }
+
+            settings_store.update(&controller.settings(), SYS_CLOCK.now());
         }
 
         asm::wfi();
@@ -158,16 +239,23 @@ fn SysTick() {
 
 #[interrupt]
 fn TIM2() {
-    // Unsafe ok, timer and recvr only used in this handler
+    // Unsafe ok, timer only used in this handler, the decoder elsewhere only within a critical section
     let timer = unsafe { IR_TIMER.as_mut().unwrap() };
     timer.clear_update_interrupt_flag();
 
-    let recvr = unsafe { IR_RECVR.as_mut().unwrap() };
-    if let Ok(Some(cmd)) = recvr.poll() {
-        let cmd = IrCommand::from(cmd);
-        if cmd.button.repeat_allowed() || !cmd.repeat {
-            let _ = unsafe { IR_CMD_QUEUE.enqueue(cmd).ok() };
-        }
+    let decoder = unsafe { IR_DECODER.as_mut().unwrap() };
+    if let Some(frame) = decoder.poll() {
+        let _ = unsafe { IR_FRAME_QUEUE.enqueue(frame).ok() };
+    }
+}
+
+#[interrupt]
+fn USART1_EXTI25() {
+    // Unsafe ok, receiver only used in this handler
+    let rx = unsafe { CONSOLE_RX.as_mut().unwrap() };
+    // Errors clear their flag, the byte is lost
+    if let Ok(byte) = rx.read() {
+        let _ = unsafe { CONSOLE_QUEUE.enqueue(byte).ok() };
     }
 }
 
@@ -0,0 +1,185 @@
+use crate::{Debouncer, Duration, Edge, Instant};
+use core::convert::Infallible;
+use embedded_hal::digital::v2::InputPin;
+use log::debug;
+
+pub const BUTTON_DEBOUNCE_DURATION: Duration = Duration::from_millis(20);
+
+/// Held at least this long is a long press, reported while still held
+pub const LONG_PRESS_DURATION: Duration = Duration::from_millis(800);
+
+/// A second press started within this window of the first release is a double press
+pub const DOUBLE_PRESS_WINDOW: Duration = Duration::from_millis(300);
+
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub enum Gesture {
+    ShortPress,
+    LongPress,
+    DoublePress,
+}
+
+/// Classifies debounced button levels into gestures, driven by timestamps
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct GestureClassifier {
+    pressed_at: Option<Instant>,
+    long_press_reported: bool,
+    released_at: Option<Instant>,
+}
+
+impl GestureClassifier {
+    pub const fn new() -> Self {
+        GestureClassifier {
+            pressed_at: None,
+            long_press_reported: false,
+            released_at: None,
+        }
+    }
+
+    pub fn update(&mut self, edge: Option<Edge>, now: Instant) -> Option<Gesture> {
+        match edge {
+            Some(Edge::Rising) => {
+                self.pressed_at = Some(now);
+                self.long_press_reported = false;
+                None
+            }
+            Some(Edge::Falling) => {
+                self.pressed_at = None;
+                if self.long_press_reported {
+                    self.long_press_reported = false;
+                    None
+                } else if self.released_at.take().is_some() {
+                    Some(Gesture::DoublePress)
+                } else {
+                    self.released_at = Some(now);
+                    None
+                }
+            }
+            None => {
+                if let Some(pressed_at) = self.pressed_at {
+                    if !self.long_press_reported
+                        && now.duration_since(pressed_at) >= LONG_PRESS_DURATION
+                    {
+                        // A short press right before is reported first,
+                        // the long press on the next update
+                        if self.released_at.take().is_some() {
+                            return Some(Gesture::ShortPress);
+                        }
+                        self.long_press_reported = true;
+                        return Some(Gesture::LongPress);
+                    }
+                } else if let Some(released_at) = self.released_at {
+                    if now.duration_since(released_at) >= DOUBLE_PRESS_WINDOW {
+                        self.released_at = None;
+                        return Some(Gesture::ShortPress);
+                    }
+                }
+                None
+            }
+        }
+    }
+}
+
+/// Push button, pulled up, the button closes to ground when pressed
+pub struct PushButton<P> {
+    pin: P,
+    debouncer: Debouncer,
+    classifier: GestureClassifier,
+}
+
+impl<P> PushButton<P>
+where
+    P: InputPin<Error = Infallible>,
+{
+    pub fn new(pin: P, now: Instant) -> Self {
+        PushButton {
+            pin,
+            debouncer: Debouncer::new(BUTTON_DEBOUNCE_DURATION, false, now),
+            classifier: GestureClassifier::new(),
+        }
+    }
+
+    /// Raw pin level, not debounced
+    pub fn is_pressed(&self) -> bool {
+        // Unwrap ok, infallible
+        self.pin.is_low().unwrap()
+    }
+
+    /// Sample the pin, call this periodically, 1~5 ms should do
+    pub fn poll(&mut self, now: Instant) -> Option<Gesture> {
+        // Unwrap ok, infallible
+        let pressed = self.pin.is_low().unwrap();
+        let edge = self.debouncer.update(pressed, now);
+        let gesture = self.classifier.update(edge, now);
+        if let Some(g) = gesture {
+            debug!("Button {:?} at {}", g, now);
+        }
+        gesture
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Presses and releases at the given milliseconds, updated every millisecond
+    /// until `end`, returns the gestures and when they came
+    fn script(presses: &[(u32, u32)], end: u32) -> [Option<(u32, Gesture)>; 4] {
+        let mut classifier = GestureClassifier::new();
+        let mut gestures = [None; 4];
+        let mut count = 0;
+        for now in 0..end {
+            let edge = if presses.iter().any(|(down, _)| *down == now) {
+                Some(Edge::Rising)
+            } else if presses.iter().any(|(_, up)| *up == now) {
+                Some(Edge::Falling)
+            } else {
+                None
+            };
+            if let Some(g) = classifier.update(edge, Instant::from_millis(now)) {
+                gestures[count] = Some((now, g));
+                count += 1;
+            }
+        }
+        gestures
+    }
+
+    #[test]
+    fn short_press() {
+        let window = DOUBLE_PRESS_WINDOW.as_millis();
+        assert_eq!(
+            script(&[(10, 100)], 1000),
+            [Some((100 + window, Gesture::ShortPress)), None, None, None]
+        );
+    }
+
+    #[test]
+    fn double_press() {
+        assert_eq!(
+            script(&[(10, 100), (200, 300)], 1000),
+            [Some((300, Gesture::DoublePress)), None, None, None]
+        );
+    }
+
+    #[test]
+    fn long_press_is_reported_while_held() {
+        let long = LONG_PRESS_DURATION.as_millis();
+        assert_eq!(
+            script(&[(10, 2000)], 3000),
+            [Some((10 + long, Gesture::LongPress)), None, None, None]
+        );
+    }
+
+    #[test]
+    fn long_press_after_short_press() {
+        let long = LONG_PRESS_DURATION.as_millis();
+        assert_eq!(
+            script(&[(10, 100), (200, 2000)], 3000),
+            [
+                Some((200 + long, Gesture::ShortPress)),
+                Some((201 + long, Gesture::LongPress)),
+                None,
+                None
+            ]
+        );
+    }
+}
@@ -0,0 +1,150 @@
+use crate::hal::pac::{PWR, RCC, RTC};
+use crate::{Clock, Duration, RealTimeClock, RtcError, TimeOfDay};
+use log::warn;
+
+const WRITE_PROTECT_KEY1: u8 = 0xCA;
+const WRITE_PROTECT_KEY2: u8 = 0x53;
+const WRITE_PROTECT_LOCK: u8 = 0xFF;
+
+/// The LSE typically starts in ~2 s, a missing crystal never does
+pub const LSE_STARTUP_TIMEOUT: Duration = Duration::from_millis(5 * 1000);
+
+/// Init mode is entered within 2 RTCCLK periods, ~61 us from the LSE
+pub const INIT_MODE_TIMEOUT: Duration = Duration::from_millis(10);
+
+/// The on-chip RTC, clocked from the 32.768 kHz LSE crystal.
+///
+/// The hal's `Rtc::new` resets the backup domain, this only does that
+/// when the RTC isn't already running from the LSE, so the time survives resets.
+/// The calendar date isn't used, the year is only set so `INITS` marks the time as valid.
+pub struct InternalRtc<C> {
+    regs: RTC,
+    clock: C,
+    /// False if the LSE didn't start, there's no time of day then
+    running: bool,
+}
+
+impl<C> InternalRtc<C>
+where
+    C: Clock,
+{
+    /// `wait` is called while the LSE starts, e.g. to feed the watchdog.
+    /// If it doesn't start within `LSE_STARTUP_TIMEOUT` the RTC isn't used,
+    /// `time_of_day` stays None
+    ///
+    /// # Safety
+    /// Modifies the RCC APB1ENR and BDCR registers, owned by the hal's rcc,
+    /// which doesn't touch the PWREN bit or the backup domain
+    pub unsafe fn new<F>(regs: RTC, pwr: PWR, clock: C, mut wait: F) -> Self
+    where
+        F: FnMut(),
+    {
+        let rcc = &*RCC::ptr();
+
+        // Backup domain write access
+        rcc.apb1enr.modify(|_, w| w.pwren().set_bit());
+        pwr.cr.modify(|_, w| w.dbp().set_bit());
+
+        let bdcr = rcc.bdcr.read();
+        if !(bdcr.rtcen().bit_is_set() && bdcr.rtcsel().is_lse()) {
+            rcc.bdcr.modify(|_, w| w.bdrst().set_bit());
+            rcc.bdcr.modify(|_, w| w.bdrst().clear_bit());
+            rcc.bdcr.modify(|_, w| w.lseon().set_bit());
+            let started_at = clock.now();
+            while rcc.bdcr.read().lserdy().is_not_ready() {
+                if clock.duration_since(started_at) >= LSE_STARTUP_TIMEOUT {
+                    warn!("LSE didn't start, no RTC");
+                    rcc.bdcr.modify(|_, w| w.lseon().clear_bit());
+                    return InternalRtc {
+                        regs,
+                        clock,
+                        running: false,
+                    };
+                }
+                wait();
+            }
+            // The reset prescalers give a 1 Hz calendar from the LSE
+            rcc.bdcr.modify(|_, w| w.rtcsel().lse().rtcen().set_bit());
+        }
+
+        InternalRtc {
+            regs,
+            clock,
+            running: true,
+        }
+    }
+
+    /// Runs `f` in init mode with the write protection disabled,
+    /// `f` isn't run if init mode isn't entered within `INIT_MODE_TIMEOUT`
+    fn modify<F>(&mut self, f: F) -> Result<(), RtcError>
+    where
+        F: FnOnce(&RTC),
+    {
+        self.regs.wpr.write(|w| w.key().bits(WRITE_PROTECT_KEY1));
+        self.regs.wpr.write(|w| w.key().bits(WRITE_PROTECT_KEY2));
+        self.regs.isr.modify(|_, w| w.init().set_bit());
+        let started_at = self.clock.now();
+        let mut result = Ok(());
+        while self.regs.isr.read().initf().is_not_allowed() {
+            if self.clock.duration_since(started_at) >= INIT_MODE_TIMEOUT {
+                result = Err(RtcError::InitTimeout);
+                break;
+            }
+        }
+        if result.is_ok() {
+            f(&self.regs);
+        }
+        // Shadow registers resync after leaving init mode
+        self.regs
+            .isr
+            .modify(|_, w| w.init().clear_bit().rsf().clear_bit());
+        self.regs.wpr.write(|w| w.key().bits(WRITE_PROTECT_LOCK));
+        result
+    }
+}
+
+impl<C> RealTimeClock for InternalRtc<C>
+where
+    C: Clock,
+{
+    fn time_of_day(&self) -> Option<TimeOfDay> {
+        if !self.running {
+            return None;
+        }
+        let isr = self.regs.isr.read();
+        if isr.inits().is_not_initalized() || isr.rsf().is_not_synced() {
+            return None;
+        }
+        let tr = self.regs.tr.read();
+        // Reading TR locks the DR shadow register until DR is read
+        let _ = self.regs.dr.read();
+        TimeOfDay::new(
+            (tr.ht().bits() * 10) + tr.hu().bits(),
+            (tr.mnt().bits() * 10) + tr.mnu().bits(),
+            (tr.st().bits() * 10) + tr.su().bits(),
+        )
+    }
+
+    /// Fails without the LSE, init mode would never be entered
+    fn set_time_of_day(&mut self, time: TimeOfDay) -> Result<(), RtcError> {
+        if !self.running {
+            return Err(RtcError::NotRunning);
+        }
+        let (hours, minutes, seconds) = (time.hours(), time.minutes(), time.seconds());
+        self.modify(|regs| {
+            regs.tr.write(|w| {
+                w.pm().clear_bit();
+                w.ht().bits(hours / 10);
+                w.hu().bits(hours % 10);
+                w.mnt().bits(minutes / 10);
+                w.mnu().bits(minutes % 10);
+                w.st().bits(seconds / 10);
+                w.su().bits(seconds % 10)
+            });
+            // Year 1, January 1st
+            regs.dr
+                .write(|w| unsafe { w.yu().bits(1).mu().bits(1).du().bits(1).wdu().bits(1) });
+            regs.cr.modify(|_, w| w.fmt().clear_bit());
+        })
+    }
+}
@@ -0,0 +1,178 @@
+use crate::{Duration, TimeOfDay, RGBW8};
+
+pub const MAX_SCHEDULE_WINDOWS: usize = 4;
+
+/// Color and on duration for an AutoOn event
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct AutoOnLight {
+    pub color: RGBW8,
+    pub duration: Duration,
+}
+
+/// What an AutoOn event does
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum AutoOnAction {
+    /// The event is ignored
+    Suppress,
+    /// The default color for `ControllerConfig::auto_on_duration`
+    Default,
+    Light(AutoOnLight),
+}
+
+/// Wall clock window, from `start` up to `end`, can span midnight.
+/// Equal start and end is the whole day
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct ScheduleWindow {
+    pub start: TimeOfDay,
+    pub end: TimeOfDay,
+    pub action: AutoOnAction,
+}
+
+impl ScheduleWindow {
+    pub fn contains(&self, time: TimeOfDay) -> bool {
+        if self.start < self.end {
+            time >= self.start && time < self.end
+        } else if self.start > self.end {
+            time >= self.start || time < self.end
+        } else {
+            true
+        }
+    }
+}
+
+/// Time of day schedule gating AutoOn, e.g. only lighting the room at night
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct Schedule {
+    windows: [Option<ScheduleWindow>; MAX_SCHEDULE_WINDOWS],
+}
+
+impl Schedule {
+    pub const fn new() -> Self {
+        Schedule {
+            windows: [None; MAX_SCHEDULE_WINDOWS],
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.windows.iter().all(|w| w.is_none())
+    }
+
+    pub fn window(&self, index: usize) -> Option<ScheduleWindow> {
+        self.windows.get(index).copied().flatten()
+    }
+
+    /// Returns false if the index is out of range
+    pub fn set_window(&mut self, index: usize, window: Option<ScheduleWindow>) -> bool {
+        match self.windows.get_mut(index) {
+            Some(w) => {
+                *w = window;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn windows(&self) -> impl Iterator<Item = &ScheduleWindow> {
+        self.windows.iter().flatten()
+    }
+
+    /// The first window containing `now` decides, AutoOn is suppressed outside the windows.
+    /// Without any windows, or until the time of day is known, AutoOn always uses the defaults
+    pub fn auto_on_action(&self, now: Option<TimeOfDay>) -> AutoOnAction {
+        match now {
+            Some(now) if !self.is_empty() => self
+                .windows()
+                .find(|w| w.contains(now))
+                .map(|w| w.action)
+                .unwrap_or(AutoOnAction::Suppress),
+            _ => AutoOnAction::Default,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::White;
+
+    fn time(hours: u8, minutes: u8) -> TimeOfDay {
+        TimeOfDay::new(hours, minutes, 0).unwrap()
+    }
+
+    fn window(start: TimeOfDay, end: TimeOfDay, action: AutoOnAction) -> ScheduleWindow {
+        ScheduleWindow { start, end, action }
+    }
+
+    const LIGHT: AutoOnAction = AutoOnAction::Light(AutoOnLight {
+        color: RGBW8 {
+            r: 32,
+            g: 0,
+            b: 0,
+            a: White(0),
+        },
+        duration: Duration::ONE_MINUTE,
+    });
+
+    #[test]
+    fn contains_within_the_day() {
+        let w = window(time(8, 0), time(17, 0), AutoOnAction::Default);
+        assert!(!w.contains(time(7, 59)));
+        assert!(w.contains(time(8, 0)));
+        assert!(w.contains(time(16, 59)));
+        assert!(!w.contains(time(17, 0)));
+    }
+
+    #[test]
+    fn contains_across_midnight() {
+        let w = window(time(20, 0), time(7, 0), AutoOnAction::Default);
+        assert!(!w.contains(time(19, 59)));
+        assert!(w.contains(time(20, 0)));
+        assert!(w.contains(TimeOfDay::MIDNIGHT));
+        assert!(w.contains(time(6, 59)));
+        assert!(!w.contains(time(7, 0)));
+        assert!(!w.contains(time(12, 0)));
+    }
+
+    #[test]
+    fn equal_start_and_end_is_the_whole_day() {
+        let w = window(time(9, 0), time(9, 0), AutoOnAction::Default);
+        assert!(w.contains(time(9, 0)));
+        assert!(w.contains(time(8, 59)));
+        assert!(w.contains(TimeOfDay::MIDNIGHT));
+    }
+
+    #[test]
+    fn auto_on_action_before_the_time_is_known() {
+        let mut schedule = Schedule::new();
+        assert_eq!(schedule.auto_on_action(None), AutoOnAction::Default);
+        assert_eq!(
+            schedule.auto_on_action(Some(time(12, 0))),
+            AutoOnAction::Default
+        );
+
+        schedule.set_window(0, Some(window(time(20, 0), time(7, 0), LIGHT)));
+        assert_eq!(schedule.auto_on_action(None), AutoOnAction::Default);
+    }
+
+    #[test]
+    fn auto_on_action_first_window_wins() {
+        let mut schedule = Schedule::new();
+        assert!(schedule.set_window(1, Some(window(time(20, 0), time(7, 0), LIGHT))));
+        assert!(schedule.set_window(
+            2,
+            Some(window(time(6, 0), time(8, 0), AutoOnAction::Default))
+        ));
+        assert!(!schedule.set_window(MAX_SCHEDULE_WINDOWS, None));
+
+        assert_eq!(schedule.auto_on_action(Some(time(23, 0))), LIGHT);
+        assert_eq!(schedule.auto_on_action(Some(time(6, 30))), LIGHT);
+        assert_eq!(
+            schedule.auto_on_action(Some(time(7, 30))),
+            AutoOnAction::Default
+        );
+        assert_eq!(
+            schedule.auto_on_action(Some(time(12, 0))),
+            AutoOnAction::Suppress
+        );
+    }
+}
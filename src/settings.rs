@@ -0,0 +1,735 @@
+use crate::{
+    AnimationSpeeds, AutoOnAction, AutoOnLight, Button, ControllerConfig, Duration, EffectId,
+    Instant, Interpolation, IrAddress, IrAddressFilter, IrKey, IrKeymap, IrProtocol, Mode,
+    Schedule, ScheduleWindow, SleepTimer, Speed, TimeOfDay, White, MAX_IR_ADDRESSES,
+    MAX_SCHEDULE_WINDOWS, NUM_BUTTONS, RGBW8,
+};
+use core::convert::TryInto;
+use log::{debug, warn};
+
+/// Flash page size of the STM32F303
+pub const SETTINGS_PAGE_SIZE: usize = 2048;
+
+/// Pages reserved for settings, the log moves to the other page when one fills up
+pub const SETTINGS_NUM_PAGES: usize = 2;
+
+/// Settings are saved once they have stopped changing for this long
+pub const SETTINGS_SAVE_DELAY: Duration = Duration::from_millis(5 * 1000);
+
+const RECORD_MAGIC: [u8; 2] = *b"NL";
+const RECORD_VERSION: u8 = 12;
+const RECORD_HEADER_SIZE: usize = 8;
+const RECORD_PAYLOAD_SIZE: usize = ADDRESSES_OFFSET + (MAX_IR_ADDRESSES * KEY_SIZE);
+const RECORD_SIZE: usize = RECORD_HEADER_SIZE + RECORD_PAYLOAD_SIZE + 4;
+const SLOTS_PER_PAGE: usize = SETTINGS_PAGE_SIZE / RECORD_SIZE;
+const ERASED: u8 = 0xFF;
+const NO_WAKE_TIME: u16 = 0xFFFF;
+
+const SCHEDULE_OFFSET: usize = 40;
+const SPEEDS_OFFSET: usize = SCHEDULE_OFFSET + (MAX_SCHEDULE_WINDOWS * WINDOW_SIZE);
+
+/// Speed bytes reserved, one per effect, the ones not used yet are left erased
+/// so new effects don't change the layout
+const MAX_EFFECTS: usize = 16;
+const _: () = assert!(EffectId::COUNT <= MAX_EFFECTS);
+
+/// Learned remote keys, one per button
+const KEYMAP_OFFSET: usize = SPEEDS_OFFSET + MAX_EFFECTS;
+/// Protocol, command and address
+const KEY_SIZE: usize = 4;
+const KEY_NONE: u8 = 0xFF;
+
+/// Accepted remote addresses, laid out like the keys without a command
+const ADDRESSES_OFFSET: usize = KEYMAP_OFFSET + (NUM_BUTTONS * KEY_SIZE);
+
+/// Start and end minutes, action, color and duration, padded to a half-word
+const WINDOW_SIZE: usize = 14;
+const WINDOW_NONE: u8 = 0xFF;
+const WINDOW_SUPPRESS: u8 = 0;
+const WINDOW_DEFAULT: u8 = 1;
+const WINDOW_LIGHT: u8 = 2;
+
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub enum FlashError {
+    Program,
+    WriteProtected,
+    Verify,
+}
+
+/// The flash pages reserved for settings, offsets are relative to the start of the first page
+pub trait SettingsFlash {
+    fn read(&mut self, offset: usize, buf: &mut [u8]);
+
+    fn erase_page(&mut self, page: usize) -> Result<(), FlashError>;
+
+    /// Offset and length are half-word aligned, the target must be erased
+    fn program(&mut self, offset: usize, data: &[u8]) -> Result<(), FlashError>;
+}
+
+impl<F: SettingsFlash + ?Sized> SettingsFlash for &mut F {
+    fn read(&mut self, offset: usize, buf: &mut [u8]) {
+        (**self).read(offset, buf)
+    }
+
+    fn erase_page(&mut self, page: usize) -> Result<(), FlashError> {
+        (**self).erase_page(page)
+    }
+
+    fn program(&mut self, offset: usize, data: &[u8]) -> Result<(), FlashError> {
+        (**self).program(offset, data)
+    }
+}
+
+/// User settings restored at boot
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Settings {
+    /// Last mode selected, resumed by Button::On
+    pub mode: Mode,
+    /// Last color selected, used when resuming Mode::ManualOn
+    pub color: RGBW8,
+    pub brightness: u8,
+    pub speeds: AnimationSpeeds,
+    pub config: ControllerConfig,
+}
+
+impl Settings {
+    fn encode(&self) -> [u8; RECORD_PAYLOAD_SIZE] {
+        let mut buf = [0; RECORD_PAYLOAD_SIZE];
+        buf[0] = self.mode.into();
+        buf[1] = self.color.r;
+        buf[2] = self.color.g;
+        buf[3] = self.color.b;
+        buf[4] = self.color.a.0;
+        buf[5] = self.brightness;
+        buf[6] = self.config.fade_mode_interpolation.into();
+        buf[7] = self.config.smooth_mode_interpolation.into();
+        for (chunk, d) in buf[8..32].chunks_exact_mut(4).zip(self.durations().iter()) {
+            chunk.copy_from_slice(&d.as_millis().to_le_bytes());
+        }
+        buf[32] = self.config.strobe_mode_frequency_hz;
+        buf[33] = self.config.strobe_mode_duty_percent;
+        buf[36] = self.config.sleep_timer.into();
+        buf[37] = self.config.ir_addresses.is_any().into();
+        let wake_time = self
+            .config
+            .wake_time
+            .map(encode_minutes)
+            .unwrap_or(NO_WAKE_TIME);
+        buf[38..40].copy_from_slice(&wake_time.to_le_bytes());
+        for (index, chunk) in buf[SCHEDULE_OFFSET..SPEEDS_OFFSET]
+            .chunks_exact_mut(WINDOW_SIZE)
+            .enumerate()
+        {
+            encode_window(self.config.schedule.window(index), chunk);
+        }
+        let speeds = &mut buf[SPEEDS_OFFSET..KEYMAP_OFFSET];
+        speeds.iter_mut().for_each(|b| *b = ERASED);
+        for (b, id) in speeds.iter_mut().zip(EffectId::enumerate()) {
+            *b = self
+                .speeds
+                .get(Mode::Effect(*id))
+                .unwrap_or_default()
+                .into();
+        }
+        for (chunk, button) in buf[KEYMAP_OFFSET..ADDRESSES_OFFSET]
+            .chunks_exact_mut(KEY_SIZE)
+            .zip(Button::enumerate())
+        {
+            match self.config.ir_keymap.key(*button) {
+                Some(key) => {
+                    chunk[0] = key.protocol.into();
+                    chunk[1] = key.command;
+                    chunk[2..4].copy_from_slice(&key.address.to_le_bytes());
+                }
+                None => chunk[0] = KEY_NONE,
+            }
+        }
+        let addresses = &mut buf[ADDRESSES_OFFSET..];
+        addresses.iter_mut().for_each(|b| *b = KEY_NONE);
+        for (chunk, address) in addresses
+            .chunks_exact_mut(KEY_SIZE)
+            .zip(self.config.ir_addresses.addresses())
+        {
+            chunk[0] = address.protocol.into();
+            chunk[2..4].copy_from_slice(&address.address.to_le_bytes());
+        }
+        buf
+    }
+
+    fn decode(buf: &[u8; RECORD_PAYLOAD_SIZE]) -> Option<Self> {
+        let wake_time = match u16::from_le_bytes([buf[38], buf[39]]) {
+            NO_WAKE_TIME => None,
+            minutes => Some(decode_minutes(minutes)?),
+        };
+        let mut schedule = Schedule::new();
+        for (index, chunk) in buf[SCHEDULE_OFFSET..SPEEDS_OFFSET]
+            .chunks_exact(WINDOW_SIZE)
+            .enumerate()
+        {
+            schedule.set_window(index, decode_window(chunk)?);
+        }
+        let mut speeds = AnimationSpeeds::default();
+        for (b, id) in buf[SPEEDS_OFFSET..KEYMAP_OFFSET]
+            .iter()
+            .zip(EffectId::enumerate())
+        {
+            let speed = match *b {
+                ERASED => Speed::default(),
+                b => Speed::from_u8(b)?,
+            };
+            speeds.set(Mode::Effect(*id), speed);
+        }
+        let mut ir_keymap = IrKeymap::new();
+        for (chunk, button) in buf[KEYMAP_OFFSET..ADDRESSES_OFFSET]
+            .chunks_exact(KEY_SIZE)
+            .zip(Button::enumerate())
+        {
+            let key = match chunk[0] {
+                KEY_NONE => None,
+                protocol => Some(IrKey {
+                    protocol: IrProtocol::from_u8(protocol)?,
+                    address: u16::from_le_bytes([chunk[2], chunk[3]]),
+                    command: chunk[1],
+                }),
+            };
+            ir_keymap.set_key(*button, key);
+        }
+        let mut ir_addresses = IrAddressFilter::empty();
+        ir_addresses.set_any(match buf[37] {
+            0 => false,
+            1 => true,
+            _ => return None,
+        });
+        for chunk in buf[ADDRESSES_OFFSET..].chunks_exact(KEY_SIZE) {
+            if chunk[0] != KEY_NONE {
+                ir_addresses.add(IrAddress {
+                    protocol: IrProtocol::from_u8(chunk[0])?,
+                    address: u16::from_le_bytes([chunk[2], chunk[3]]),
+                });
+            }
+        }
+        let mut durations = [Duration::ZERO; 6];
+        for (d, chunk) in durations.iter_mut().zip(buf[8..32].chunks_exact(4)) {
+            *d = Duration::from_millis(u32::from_le_bytes(chunk.try_into().ok()?));
+        }
+        let config = ControllerConfig {
+            auto_on_duration: durations[0],
+            manual_on_duration: durations[1],
+            onoff_fade_duration: durations[2],
+            flash_mode_hold_duration: durations[3],
+            strobe_mode_frequency_hz: buf[32],
+            strobe_mode_duty_percent: buf[33],
+            fade_mode_fade_duration: durations[4],
+            smooth_mode_fade_duration: durations[5],
+            fade_mode_interpolation: Interpolation::from_u8(buf[6])?,
+            smooth_mode_interpolation: Interpolation::from_u8(buf[7])?,
+            sleep_timer: SleepTimer::from_u8(buf[36])?,
+            wake_time,
+            schedule,
+            ir_keymap,
+            ir_addresses,
+        };
+        config.validate().ok()?;
+        Some(Settings {
+            mode: Mode::from_u8(buf[0])?,
+            color: RGBW8::new_alpha(buf[1], buf[2], buf[3], White(buf[4])),
+            brightness: buf[5],
+            speeds,
+            config,
+        })
+    }
+
+    fn durations(&self) -> [Duration; 6] {
+        let c = &self.config;
+        [
+            c.auto_on_duration,
+            c.manual_on_duration,
+            c.onoff_fade_duration,
+            c.flash_mode_hold_duration,
+            c.fade_mode_fade_duration,
+            c.smooth_mode_fade_duration,
+        ]
+    }
+}
+
+/// Log-structured settings store.
+///
+/// Each save appends a versioned, CRC-checked record to the active page.
+/// When the page is full the other page is erased and the log continues there,
+/// the record with the highest sequence number wins on load.
+pub struct SettingsStore<F> {
+    flash: F,
+    seq: u32,
+    /// Next free (page, slot), None if unknown or full
+    next_slot: Option<(usize, usize)>,
+    page: usize,
+    saved: Option<Settings>,
+    pending: Option<(Settings, Instant)>,
+}
+
+impl<F> SettingsStore<F>
+where
+    F: SettingsFlash,
+{
+    pub fn new(flash: F) -> Self {
+        SettingsStore {
+            flash,
+            seq: 0,
+            next_slot: None,
+            page: 0,
+            saved: None,
+            pending: None,
+        }
+    }
+
+    /// Scan the log, returns the latest valid settings
+    pub fn load(&mut self) -> Option<Settings> {
+        let mut latest: Option<(u32, usize, Settings)> = None;
+        let mut record = [0; RECORD_SIZE];
+
+        for page in 0..SETTINGS_NUM_PAGES {
+            for slot in 0..SLOTS_PER_PAGE {
+                self.flash.read(Self::offset(page, slot), &mut record);
+                if let Some((seq, settings)) = decode_record(&record) {
+                    if latest.map(|(s, _, _)| seq > s).unwrap_or(true) {
+                        latest = Some((seq, page, settings));
+                    }
+                }
+            }
+        }
+
+        if let Some((seq, page, settings)) = latest {
+            debug!("Loaded settings record {} from page {}", seq, page);
+            self.seq = seq;
+            self.page = page;
+            self.saved = Some(settings);
+        } else {
+            debug!("No valid settings record");
+        }
+        self.next_slot = self.find_free_slot(self.page);
+        self.saved
+    }
+
+    /// Call this periodically, settings are saved once they stop changing
+    pub fn update(&mut self, settings: &Settings, now: Instant) {
+        if self.saved.as_ref() == Some(settings) {
+            self.pending = None;
+            return;
+        }
+
+        match self.pending {
+            Some((pending, changed_at)) if pending == *settings => {
+                if now.duration_since(changed_at) >= SETTINGS_SAVE_DELAY {
+                    self.pending = None;
+                    if let Err(e) = self.save(settings) {
+                        warn!("Failed to save settings {:?}", e);
+                    }
+                }
+            }
+            _ => self.pending = Some((*settings, now)),
+        }
+    }
+
+    pub fn save(&mut self, settings: &Settings) -> Result<(), FlashError> {
+        let (page, slot) = match self.next_slot {
+            Some(next) => next,
+            None => {
+                let page = (self.page + 1) % SETTINGS_NUM_PAGES;
+                debug!("Settings page {} full, erasing page {}", self.page, page);
+                self.flash.erase_page(page)?;
+                (page, 0)
+            }
+        };
+
+        self.seq = self.seq.wrapping_add(1);
+        let record = encode_record(self.seq, settings);
+        let offset = Self::offset(page, slot);
+        let result = self.flash.program(offset, &record).and_then(|_| {
+            let mut readback = [0; RECORD_SIZE];
+            self.flash.read(offset, &mut readback);
+            if readback == record {
+                Ok(())
+            } else {
+                Err(FlashError::Verify)
+            }
+        });
+
+        // A failed slot is skipped, it won't pass the CRC check on load
+        self.page = page;
+        self.next_slot = if slot + 1 < SLOTS_PER_PAGE {
+            Some((page, slot + 1))
+        } else {
+            None
+        };
+
+        result?;
+        debug!(
+            "Saved settings record {} to page {} slot {}",
+            self.seq, page, slot
+        );
+        self.saved = Some(*settings);
+        Ok(())
+    }
+
+    /// First slot after the last programmed slot of the page
+    fn find_free_slot(&mut self, page: usize) -> Option<(usize, usize)> {
+        let mut record = [0; RECORD_SIZE];
+        let mut free = Some(0);
+        for slot in 0..SLOTS_PER_PAGE {
+            self.flash.read(Self::offset(page, slot), &mut record);
+            if record.iter().any(|b| *b != ERASED) {
+                free = if slot + 1 < SLOTS_PER_PAGE {
+                    Some(slot + 1)
+                } else {
+                    None
+                };
+            }
+        }
+        free.map(|slot| (page, slot))
+    }
+
+    fn offset(page: usize, slot: usize) -> usize {
+        (page * SETTINGS_PAGE_SIZE) + (slot * RECORD_SIZE)
+    }
+}
+
+fn encode_minutes(time: TimeOfDay) -> u16 {
+    (time.hours() as u16 * 60) + time.minutes() as u16
+}
+
+fn decode_minutes(minutes: u16) -> Option<TimeOfDay> {
+    TimeOfDay::new((minutes / 60) as u8, (minutes % 60) as u8, 0)
+}
+
+fn encode_window(window: Option<ScheduleWindow>, buf: &mut [u8]) {
+    let window = match window {
+        Some(w) => w,
+        None => {
+            buf[4] = WINDOW_NONE;
+            return;
+        }
+    };
+    buf[0..2].copy_from_slice(&encode_minutes(window.start).to_le_bytes());
+    buf[2..4].copy_from_slice(&encode_minutes(window.end).to_le_bytes());
+    match window.action {
+        AutoOnAction::Suppress => buf[4] = WINDOW_SUPPRESS,
+        AutoOnAction::Default => buf[4] = WINDOW_DEFAULT,
+        AutoOnAction::Light(light) => {
+            buf[4] = WINDOW_LIGHT;
+            buf[5] = light.color.r;
+            buf[6] = light.color.g;
+            buf[7] = light.color.b;
+            buf[8] = light.color.a.0;
+            buf[9..13].copy_from_slice(&light.duration.as_millis().to_le_bytes());
+        }
+    }
+}
+
+/// None if invalid, Some(None) for an unused window
+fn decode_window(buf: &[u8]) -> Option<Option<ScheduleWindow>> {
+    let action = match buf[4] {
+        WINDOW_NONE => return Some(None),
+        WINDOW_SUPPRESS => AutoOnAction::Suppress,
+        WINDOW_DEFAULT => AutoOnAction::Default,
+        WINDOW_LIGHT => AutoOnAction::Light(AutoOnLight {
+            color: RGBW8::new_alpha(buf[5], buf[6], buf[7], White(buf[8])),
+            duration: Duration::from_millis(u32::from_le_bytes(buf[9..13].try_into().ok()?)),
+        }),
+        _ => return None,
+    };
+    Some(Some(ScheduleWindow {
+        start: decode_minutes(u16::from_le_bytes([buf[0], buf[1]]))?,
+        end: decode_minutes(u16::from_le_bytes([buf[2], buf[3]]))?,
+        action,
+    }))
+}
+
+fn encode_record(seq: u32, settings: &Settings) -> [u8; RECORD_SIZE] {
+    let mut record = [0; RECORD_SIZE];
+    record[0..2].copy_from_slice(&RECORD_MAGIC);
+    record[2] = RECORD_VERSION;
+    record[3] = RECORD_PAYLOAD_SIZE as u8;
+    record[4..8].copy_from_slice(&seq.to_le_bytes());
+    record[RECORD_HEADER_SIZE..RECORD_SIZE - 4].copy_from_slice(&settings.encode());
+    let crc = crc32(&record[..RECORD_SIZE - 4]);
+    record[RECORD_SIZE - 4..].copy_from_slice(&crc.to_le_bytes());
+    record
+}
+
+fn decode_record(record: &[u8; RECORD_SIZE]) -> Option<(u32, Settings)> {
+    if record[0..2] != RECORD_MAGIC
+        || record[2] != RECORD_VERSION
+        || record[3] as usize != RECORD_PAYLOAD_SIZE
+    {
+        return None;
+    }
+    let crc = u32::from_le_bytes(record[RECORD_SIZE - 4..].try_into().ok()?);
+    if crc != crc32(&record[..RECORD_SIZE - 4]) {
+        return None;
+    }
+    let seq = u32::from_le_bytes(record[4..8].try_into().ok()?);
+    let payload = record[RECORD_HEADER_SIZE..RECORD_SIZE - 4]
+        .try_into()
+        .ok()?;
+    Settings::decode(payload).map(|s| (seq, s))
+}
+
+/// CRC-32 (IEEE 802.3), bitwise, the records are small
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0_u32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Button, IrKey, STOCK_REMOTE};
+
+    const FLASH_SIZE: usize = SETTINGS_PAGE_SIZE * SETTINGS_NUM_PAGES;
+
+    /// RAM-backed flash, programming only clears bits like the real thing
+    struct RamFlash {
+        mem: [u8; FLASH_SIZE],
+        erases: usize,
+    }
+
+    impl RamFlash {
+        fn new() -> Self {
+            RamFlash {
+                mem: [ERASED; FLASH_SIZE],
+                erases: 0,
+            }
+        }
+    }
+
+    impl SettingsFlash for RamFlash {
+        fn read(&mut self, offset: usize, buf: &mut [u8]) {
+            buf.copy_from_slice(&self.mem[offset..offset + buf.len()]);
+        }
+
+        fn erase_page(&mut self, page: usize) -> Result<(), FlashError> {
+            let start = page * SETTINGS_PAGE_SIZE;
+            self.mem[start..start + SETTINGS_PAGE_SIZE]
+                .iter_mut()
+                .for_each(|b| *b = ERASED);
+            self.erases += 1;
+            Ok(())
+        }
+
+        fn program(&mut self, offset: usize, data: &[u8]) -> Result<(), FlashError> {
+            // Half-word programming
+            assert_eq!((offset % 2, data.len() % 2), (0, 0));
+            let target = &mut self.mem[offset..offset + data.len()];
+            if target.iter().any(|b| *b != ERASED) {
+                return Err(FlashError::Program);
+            }
+            target.copy_from_slice(data);
+            Ok(())
+        }
+    }
+
+    fn settings(brightness: u8) -> Settings {
+        Settings {
+            mode: Mode::ManualOn,
+            color: RGBW8::new_alpha(1, 2, 3, White(4)),
+            brightness,
+            speeds: AnimationSpeeds::default(),
+            config: ControllerConfig::default(),
+        }
+    }
+
+    fn slot_offset(page: usize, slot: usize) -> usize {
+        SettingsStore::<RamFlash>::offset(page, slot)
+    }
+
+    #[test]
+    fn erased_flash_has_no_settings() {
+        let mut store = SettingsStore::new(RamFlash::new());
+        assert_eq!(store.load(), None);
+        assert_eq!(store.next_slot, Some((0, 0)));
+    }
+
+    #[test]
+    fn encode_decode_round_trip() {
+        let mut s = settings(200);
+        s.mode = Mode::Effect(EffectId::Smooth);
+        s.config.auto_on_duration = Duration::TEN_MINUTES;
+        s.config.manual_on_duration = Duration::ONE_HOUR;
+        s.config.fade_mode_fade_duration = Duration::from_millis(250);
+        s.config.flash_mode_hold_duration = Duration::from_millis(300);
+        s.config.strobe_mode_frequency_hz = 10;
+        s.config.strobe_mode_duty_percent = 20;
+        s.speeds.set(Mode::Effect(EffectId::Fade), Speed::Slowest);
+        s.speeds.set(Mode::Effect(EffectId::Comet), Speed::Fastest);
+        s.config.wake_time = TimeOfDay::new(6, 45, 0);
+        let window = |start: u8, end: u8, action| ScheduleWindow {
+            start: TimeOfDay::new(start, 30, 0).unwrap(),
+            end: TimeOfDay::new(end, 0, 0).unwrap(),
+            action,
+        };
+        s.config
+            .schedule
+            .set_window(0, Some(window(20, 7, AutoOnAction::Suppress)));
+        s.config.schedule.set_window(
+            2,
+            Some(window(
+                22,
+                6,
+                AutoOnAction::Light(AutoOnLight {
+                    color: RGBW8::new_alpha(5, 6, 7, White(8)),
+                    duration: Duration::ONE_MINUTE,
+                }),
+            )),
+        );
+        s.config
+            .schedule
+            .set_window(3, Some(window(6, 8, AutoOnAction::Default)));
+        let key = |protocol, address, command| {
+            Some(IrKey {
+                protocol,
+                address,
+                command,
+            })
+        };
+        s.config
+            .ir_keymap
+            .set_key(Button::On, key(IrProtocol::Nec16, 0x1234, 7));
+        s.config
+            .ir_keymap
+            .set_key(Button::Smooth, key(IrProtocol::Rc6, 0xFF, 0xFF));
+        let filter = &mut s.config.ir_addresses;
+        filter.add(IrAddress {
+            protocol: IrProtocol::Samsung,
+            address: 0xBEEF,
+        });
+        filter.remove(STOCK_REMOTE);
+        filter.set_any(true);
+
+        assert_eq!(Settings::decode(&s.encode()), Some(s));
+    }
+
+    #[test]
+    fn invalid_config_isnt_decoded() {
+        let mut s = settings(200);
+        s.config.flash_mode_hold_duration = Duration::ZERO;
+        assert_eq!(Settings::decode(&s.encode()), None);
+
+        let mut s = settings(200);
+        s.config.strobe_mode_duty_percent = 100;
+        assert_eq!(Settings::decode(&s.encode()), None);
+    }
+
+    #[test]
+    fn latest_record_is_loaded() {
+        let mut store = SettingsStore::new(RamFlash::new());
+        store.load();
+        for brightness in 1..=3 {
+            store.save(&settings(brightness)).unwrap();
+        }
+
+        let mut store = SettingsStore::new(store.flash);
+        assert_eq!(store.load(), Some(settings(3)));
+        assert_eq!(store.next_slot, Some((0, 3)));
+    }
+
+    #[test]
+    fn full_page_rolls_over_to_the_other_page() {
+        let mut store = SettingsStore::new(RamFlash::new());
+        store.load();
+        for brightness in 0..=SLOTS_PER_PAGE {
+            store.save(&settings(brightness as u8)).unwrap();
+        }
+        assert_eq!(store.flash.erases, 1);
+        assert_eq!(store.page, 1);
+        assert_eq!(store.next_slot, Some((1, 1)));
+
+        // Page 0 still holds the older records, the highest sequence number wins
+        let mut store = SettingsStore::new(store.flash);
+        assert_eq!(store.load(), Some(settings(SLOTS_PER_PAGE as u8)));
+        assert_eq!(store.page, 1);
+        assert_eq!(store.next_slot, Some((1, 1)));
+    }
+
+    #[test]
+    fn log_wraps_around_both_pages() {
+        let mut store = SettingsStore::new(RamFlash::new());
+        store.load();
+        let saves = (2 * SLOTS_PER_PAGE) + 2;
+        for brightness in 0..saves {
+            store.save(&settings(brightness as u8)).unwrap();
+        }
+        assert_eq!(store.flash.erases, 2);
+        assert_eq!(store.page, 0);
+
+        let mut store = SettingsStore::new(store.flash);
+        assert_eq!(store.load(), Some(settings((saves - 1) as u8)));
+        assert_eq!(store.seq, saves as u32);
+        assert_eq!(store.next_slot, Some((0, 2)));
+    }
+
+    #[test]
+    fn corrupt_record_is_skipped() {
+        let mut store = SettingsStore::new(RamFlash::new());
+        store.load();
+        store.save(&settings(1)).unwrap();
+        store.save(&settings(2)).unwrap();
+
+        let mut flash = store.flash;
+        flash.mem[slot_offset(0, 1) + RECORD_HEADER_SIZE + 5] ^= 0x01;
+        let mut store = SettingsStore::new(flash);
+        assert_eq!(store.load(), Some(settings(1)));
+        // The corrupt slot isn't reused
+        assert_eq!(store.next_slot, Some((0, 2)));
+    }
+
+    #[test]
+    fn torn_record_is_skipped() {
+        let mut store = SettingsStore::new(RamFlash::new());
+        store.load();
+        store.save(&settings(1)).unwrap();
+
+        // Power lost half way through programming the second record
+        let record = encode_record(2, &settings(2));
+        let half = RECORD_SIZE / 2;
+        let mut flash = store.flash;
+        flash.program(slot_offset(0, 1), &record[..half]).unwrap();
+
+        let mut store = SettingsStore::new(flash);
+        assert_eq!(store.load(), Some(settings(1)));
+        store.save(&settings(3)).unwrap();
+        let mut store = SettingsStore::new(store.flash);
+        assert_eq!(store.load(), Some(settings(3)));
+    }
+
+    #[test]
+    fn update_saves_once_settings_stop_changing() {
+        let mut store = SettingsStore::new(RamFlash::new());
+        store.load();
+        let delay = SETTINGS_SAVE_DELAY.as_millis();
+
+        store.update(&settings(1), Instant::from_millis(0));
+        store.update(&settings(1), Instant::from_millis(delay - 1));
+        assert_eq!(store.saved, None);
+
+        // A change restarts the delay
+        store.update(&settings(2), Instant::from_millis(delay - 1));
+        store.update(&settings(2), Instant::from_millis(delay));
+        assert_eq!(store.saved, None);
+        store.update(&settings(2), Instant::from_millis((2 * delay) - 2));
+        assert_eq!(store.saved, None);
+        store.update(&settings(2), Instant::from_millis((2 * delay) - 1));
+        assert_eq!(store.saved, Some(settings(2)));
+        assert_eq!(store.next_slot, Some((0, 1)));
+
+        // Nothing to save when unchanged
+        store.update(&settings(2), Instant::from_millis(4 * delay));
+        assert_eq!(store.next_slot, Some((0, 1)));
+    }
+}
@@ -0,0 +1,71 @@
+use crate::{EffectId, Mode};
+
+/// Animation speed steps, each one twice or half as fast as the next
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Default)]
+pub enum Speed {
+    Slowest,
+    Slower,
+    #[default]
+    Normal,
+    Faster,
+    Fastest,
+}
+
+impl Speed {
+    pub fn from_u8(value: u8) -> Option<Self> {
+        use Speed::*;
+        Some(match value {
+            0 => Slowest,
+            1 => Slower,
+            2 => Normal,
+            3 => Faster,
+            4 => Fastest,
+            _ => return None,
+        })
+    }
+
+    /// Faster, wraps around to Speed::Slowest
+    pub fn next(self) -> Self {
+        Speed::from_u8(self as u8 + 1).unwrap_or(Speed::Slowest)
+    }
+
+    /// Effect time per clock time, in quarters
+    pub fn rate(self) -> u32 {
+        match self {
+            Speed::Slowest => 1,
+            Speed::Slower => 2,
+            Speed::Normal => 4,
+            Speed::Faster => 8,
+            Speed::Fastest => 16,
+        }
+    }
+}
+
+impl From<Speed> for u8 {
+    fn from(s: Speed) -> Self {
+        s as u8
+    }
+}
+
+/// Speed of each effect, see `EffectClock`
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct AnimationSpeeds {
+    speeds: [Speed; EffectId::COUNT],
+}
+
+impl AnimationSpeeds {
+    /// None if the mode isn't an effect
+    pub fn get(&self, mode: Mode) -> Option<Speed> {
+        match mode {
+            Mode::Effect(id) => Some(self.speeds[id as usize]),
+            _ => None,
+        }
+    }
+
+    /// Ignored if the mode isn't an effect
+    pub fn set(&mut self, mode: Mode, speed: Speed) {
+        if let Mode::Effect(id) = mode {
+            self.speeds[id as usize] = speed;
+        }
+    }
+}
@@ -0,0 +1,168 @@
+use crate::{Duration, TimeOfDay, White, RGBW, RGBW8};
+use log::debug;
+
+/// The ramp starts this long before the wake time
+pub const SUNRISE_DURATION: Duration = Duration::from_millis(30 * 60 * 1000);
+
+/// Sunrise ramp, deep red through orange to bright white.
+/// Perceptual values, each step fades evenly into the next
+pub const SUNRISE_COLORS: [RGBW8; 4] = [
+    RGBW {
+        r: 96,
+        g: 0,
+        b: 0,
+        a: White(0),
+    },
+    RGBW {
+        r: 255,
+        g: 96,
+        b: 0,
+        a: White(0),
+    },
+    RGBW {
+        r: 255,
+        g: 160,
+        b: 48,
+        a: White(160),
+    },
+    RGBW {
+        r: 192,
+        g: 160,
+        b: 128,
+        a: White(255),
+    },
+];
+
+/// The step after `color` in the ramp, None once it's reached bright white
+pub fn next_sunrise_color(color: &RGBW8) -> Option<RGBW8> {
+    SUNRISE_COLORS
+        .iter()
+        .position(|c| c == color)
+        .and_then(|i| SUNRISE_COLORS.get(i + 1))
+        .copied()
+}
+
+/// Duration of each step, the ramp fades from the current color into the first step
+/// and reaches the last one at the wake time
+pub fn sunrise_step_duration(until_wake: Duration) -> Duration {
+    Duration::from_millis(until_wake.as_millis() / SUNRISE_COLORS.len() as u32)
+}
+
+/// Decides when the sunrise ramp starts, fires at most once per wake time
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct SunriseAlarm {
+    triggered: bool,
+}
+
+impl SunriseAlarm {
+    pub const fn new() -> Self {
+        SunriseAlarm { triggered: false }
+    }
+
+    /// Call this periodically, returns the time left until `wake_time` once the ramp should start.
+    /// Starting late, e.g. after a reset, gives a shorter ramp, a missed wake time is skipped
+    pub fn poll(&mut self, wake_time: TimeOfDay, now: TimeOfDay) -> Option<Duration> {
+        let until_wake = now.duration_until(wake_time);
+        if until_wake == Duration::ZERO || until_wake > SUNRISE_DURATION {
+            self.triggered = false;
+            None
+        } else if !self.triggered {
+            debug!("Sunrise at {}, wake time {}", now, wake_time);
+            self.triggered = true;
+            Some(until_wake)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MockRealTimeClock, RealTimeClock};
+
+    fn time(hours: u8, minutes: u8) -> TimeOfDay {
+        TimeOfDay::new(hours, minutes, 0).unwrap()
+    }
+
+    /// Polls once a second for `seconds` from `start`,
+    /// returns how often the alarm fired, the first time and its time until wake
+    fn run(
+        wake_time: TimeOfDay,
+        start: TimeOfDay,
+        seconds: u32,
+    ) -> (usize, Option<(TimeOfDay, Duration)>) {
+        let mut rtc = MockRealTimeClock::new();
+        rtc.set_time_of_day(start).unwrap();
+        let mut alarm = SunriseAlarm::new();
+        let mut fired = 0;
+        let mut first = None;
+        for _ in 0..seconds {
+            let now = rtc.time_of_day().unwrap();
+            if let Some(until_wake) = alarm.poll(wake_time, now) {
+                fired += 1;
+                first.get_or_insert((now, until_wake));
+            }
+            rtc.advance(Duration::ONE_SECOND);
+        }
+        (fired, first)
+    }
+
+    #[test]
+    fn fires_once_per_wake_time() {
+        let (fired, first) = run(time(7, 0), time(6, 0), 2 * 60 * 60);
+        assert_eq!(fired, 1);
+        assert_eq!(first, Some((time(6, 30), SUNRISE_DURATION)));
+
+        // Once a day
+        let (fired, _) = run(time(7, 0), time(6, 0), 2 * 24 * 60 * 60);
+        assert_eq!(fired, 2);
+    }
+
+    #[test]
+    fn fires_across_midnight() {
+        let (fired, first) = run(time(0, 10), time(23, 30), 60 * 60);
+        assert_eq!(fired, 1);
+        assert_eq!(first, Some((time(23, 40), SUNRISE_DURATION)));
+    }
+
+    #[test]
+    fn late_start_gives_a_shorter_ramp() {
+        let (fired, first) = run(time(7, 0), time(6, 50), 60 * 60);
+        assert_eq!(fired, 1);
+        assert_eq!(first, Some((time(6, 50), Duration::TEN_MINUTES)));
+    }
+
+    #[test]
+    fn missed_wake_time_is_skipped() {
+        let (fired, _) = run(time(7, 0), time(7, 0), 60 * 60);
+        assert_eq!(fired, 0);
+        let (fired, _) = run(time(7, 0), time(7, 5), 60 * 60);
+        assert_eq!(fired, 0);
+    }
+
+    #[test]
+    fn ramp_reaches_white_at_the_wake_time() {
+        let white = SUNRISE_COLORS[SUNRISE_COLORS.len() - 1];
+        assert_eq!(white.a, White(255));
+
+        for until_wake in [
+            SUNRISE_DURATION,
+            Duration::TEN_MINUTES,
+            Duration::from_millis(999),
+        ] {
+            let step = sunrise_step_duration(until_wake);
+            // The fade into the first color is a step too
+            let mut color = SUNRISE_COLORS[0];
+            let mut elapsed = step.as_millis();
+            while let Some(next) = next_sunrise_color(&color) {
+                color = next;
+                elapsed += step.as_millis();
+            }
+            assert_eq!(color, white);
+            assert!(elapsed <= until_wake.as_millis());
+            assert!(until_wake.as_millis() - elapsed < SUNRISE_COLORS.len() as u32);
+        }
+        assert_eq!(next_sunrise_color(&white), None);
+    }
+}
@@ -4,8 +4,13 @@ use hal::rcc::Clocks;
 use hal::stm32::SYST;
 use log::debug;
 
-/// Milliseconds
-#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+/// Milliseconds, wraps around every ~49.7 days.
+/// Instants are only compared through `duration_since`, which is wrap-safe
+/// for durations up to `Instant::MAX_DURATION`.
+///
+/// `Ord` is there for `Duration`, which shares the type. It must not be used
+/// to order instants, a later instant past the wrap around compares as smaller.
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Default)]
 #[repr(transparent)]
 pub struct Instant(u32);
 
@@ -18,6 +23,8 @@ impl Instant {
     pub const TEN_MINUTES: Self = Instant(1000 * 60 * 10);
     pub const ONE_HOUR: Self = Instant(1000 * 60 * 60);
     pub const TEN_HOURS: Self = Instant(1000 * 60 * 60 * 10);
+    /// Longest duration measurable across the wrap around, ~24.8 days
+    pub const MAX_DURATION: Self = Instant(i32::MAX as u32);
 
     pub const fn from_millis(ms: u32) -> Self {
         Instant(ms)
@@ -27,12 +34,14 @@ impl Instant {
         self.0
     }
 
+    /// Wrap-safe, an `earlier` that is actually later than self saturates to zero
     pub fn duration_since(&self, earlier: Instant) -> Duration {
-        Duration::from_millis(
-            self.0
-                .checked_sub(earlier.0)
-                .expect("Supplied instant is later than self"),
-        )
+        let elapsed = self.0.wrapping_sub(earlier.0);
+        if elapsed > Self::MAX_DURATION.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_millis(elapsed)
+        }
     }
 }
 
@@ -52,11 +61,26 @@ impl ops::Add for Instant {
     type Output = Self;
 
     fn add(self, other: Self) -> Self {
-        Instant(self.0 + other.0)
+        Instant(self.0.wrapping_add(other.0))
     }
 }
 
-/// 32-bit millisecond clock
+/// A source of millisecond time
+pub trait Clock {
+    fn now(&self) -> Instant;
+
+    fn duration_since(&self, earlier: Instant) -> Duration {
+        self.now().duration_since(earlier)
+    }
+}
+
+impl<C: Clock + ?Sized> Clock for &C {
+    fn now(&self) -> Instant {
+        (**self).now()
+    }
+}
+
+/// 32-bit millisecond clock, driven by the SysTick interrupt
 #[derive(Debug)]
 pub struct SystemClock(AtomicU32);
 
@@ -64,8 +88,6 @@ unsafe impl Send for SystemClock {}
 unsafe impl Sync for SystemClock {}
 
 impl SystemClock {
-    pub const NEAR_WRAP_AROUND_VALUE: Instant = Instant(core::u32::MAX - Instant::TEN_HOURS.0);
-
     pub const fn new() -> Self {
         SystemClock(AtomicU32::new(0))
     }
@@ -87,19 +109,83 @@ impl SystemClock {
         self.0.fetch_add(1, SeqCst);
     }
 
-    pub fn is_near_wrap_around(&self) -> bool {
-        self.now().as_millis() >= Self::NEAR_WRAP_AROUND_VALUE.as_millis()
+    fn load(&self) -> u32 {
+        self.0.load(SeqCst)
     }
+}
 
-    pub fn now(&self) -> Instant {
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
         Instant::from_millis(self.load())
     }
+}
 
-    pub fn duration_since(&self, earlier: Instant) -> Duration {
-        self.now().duration_since(earlier)
+/// Manually advanced clock, for driving the controller off-target
+#[derive(Debug, Default)]
+pub struct MockClock(AtomicU32);
+
+impl MockClock {
+    pub const fn new() -> Self {
+        MockClock(AtomicU32::new(0))
     }
 
-    fn load(&self) -> u32 {
-        self.0.load(SeqCst)
+    pub fn set(&self, now: Instant) {
+        self.0.store(now.as_millis(), SeqCst);
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        self.0.fetch_add(duration.as_millis(), SeqCst);
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> Instant {
+        Instant::from_millis(self.0.load(SeqCst))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn duration_since_across_the_wrap_around() {
+        let earlier = Instant::from_millis(u32::MAX - 9);
+        let later = Instant::from_millis(20);
+        assert_eq!(later.duration_since(earlier), Duration::from_millis(30));
+        assert_eq!(
+            Instant::from_millis(0).duration_since(Instant::from_millis(u32::MAX)),
+            Duration::from_millis(1)
+        );
+        assert_eq!(
+            (earlier + Duration::from_millis(30)).duration_since(earlier),
+            Duration::from_millis(30)
+        );
+    }
+
+    #[test]
+    fn duration_since_saturates_past_max_duration() {
+        let earlier = Instant::from_millis(u32::MAX - 9);
+        let at_max = earlier + Instant::MAX_DURATION;
+        assert_eq!(at_max.duration_since(earlier), Instant::MAX_DURATION);
+        assert_eq!(
+            (at_max + Duration::from_millis(1)).duration_since(earlier),
+            Duration::ZERO
+        );
+        // An earlier that is actually later
+        assert_eq!(
+            Instant::from_millis(10).duration_since(Instant::from_millis(11)),
+            Duration::ZERO
+        );
+    }
+
+    #[test]
+    fn mock_clock_advances_across_the_wrap_around() {
+        let clock = MockClock::new();
+        let start = Instant::from_millis(u32::MAX - 4);
+        clock.set(start);
+        clock.advance(Duration::from_millis(10));
+        assert_eq!(clock.now(), Instant::from_millis(5));
+        assert_eq!(clock.duration_since(start), Duration::from_millis(10));
     }
 }
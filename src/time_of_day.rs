@@ -0,0 +1,182 @@
+use crate::Duration;
+use core::fmt;
+
+pub const SECONDS_PER_DAY: u32 = 24 * 60 * 60;
+
+/// Wall clock time, 24 hour, seconds resolution
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub struct TimeOfDay {
+    hours: u8,
+    minutes: u8,
+    seconds: u8,
+}
+
+impl TimeOfDay {
+    pub const MIDNIGHT: Self = TimeOfDay {
+        hours: 0,
+        minutes: 0,
+        seconds: 0,
+    };
+
+    pub fn new(hours: u8, minutes: u8, seconds: u8) -> Option<Self> {
+        if hours < 24 && minutes < 60 && seconds < 60 {
+            Some(TimeOfDay {
+                hours,
+                minutes,
+                seconds,
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Wraps around midnight
+    pub fn from_seconds(seconds: u32) -> Self {
+        let seconds = seconds % SECONDS_PER_DAY;
+        TimeOfDay {
+            hours: (seconds / 3600) as u8,
+            minutes: ((seconds / 60) % 60) as u8,
+            seconds: (seconds % 60) as u8,
+        }
+    }
+
+    pub fn hours(self) -> u8 {
+        self.hours
+    }
+
+    pub fn minutes(self) -> u8 {
+        self.minutes
+    }
+
+    pub fn seconds(self) -> u8 {
+        self.seconds
+    }
+
+    pub fn seconds_since_midnight(self) -> u32 {
+        (self.hours as u32 * 3600) + (self.minutes as u32 * 60) + self.seconds as u32
+    }
+
+    /// Time until `later` is next reached, zero if they're equal
+    pub fn duration_until(self, later: TimeOfDay) -> Duration {
+        let secs = (later.seconds_since_midnight() + SECONDS_PER_DAY
+            - self.seconds_since_midnight())
+            % SECONDS_PER_DAY;
+        Duration::from_millis(secs * 1000)
+    }
+}
+
+impl fmt::Display for TimeOfDay {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:02}:{:02}:{:02}",
+            self.hours, self.minutes, self.seconds
+        )
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub enum RtcError {
+    /// The clock source didn't start
+    NotRunning,
+    /// The calendar couldn't be stopped to set it
+    InitTimeout,
+}
+
+/// Calendar clock, keeps the time of day across resets
+pub trait RealTimeClock {
+    /// None until the time has been set
+    fn time_of_day(&self) -> Option<TimeOfDay>;
+
+    fn set_time_of_day(&mut self, time: TimeOfDay) -> Result<(), RtcError>;
+}
+
+impl<R: RealTimeClock + ?Sized> RealTimeClock for &mut R {
+    fn time_of_day(&self) -> Option<TimeOfDay> {
+        (**self).time_of_day()
+    }
+
+    fn set_time_of_day(&mut self, time: TimeOfDay) -> Result<(), RtcError> {
+        (**self).set_time_of_day(time)
+    }
+}
+
+/// A calendar clock driven by hand, for the simulator and tests
+#[derive(Default)]
+pub struct MockRealTimeClock {
+    time: Option<TimeOfDay>,
+}
+
+impl MockRealTimeClock {
+    pub fn new() -> Self {
+        MockRealTimeClock::default()
+    }
+
+    /// Wraps around midnight
+    pub fn advance(&mut self, duration: Duration) {
+        if let Some(time) = self.time {
+            self.time = Some(TimeOfDay::from_seconds(
+                time.seconds_since_midnight() + (duration.as_millis() / 1000),
+            ));
+        }
+    }
+}
+
+impl RealTimeClock for MockRealTimeClock {
+    fn time_of_day(&self) -> Option<TimeOfDay> {
+        self.time
+    }
+
+    fn set_time_of_day(&mut self, time: TimeOfDay) -> Result<(), RtcError> {
+        self.time = Some(time);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time(hours: u8, minutes: u8) -> TimeOfDay {
+        TimeOfDay::new(hours, minutes, 0).unwrap()
+    }
+
+    #[test]
+    fn duration_until() {
+        assert_eq!(
+            time(6, 0).duration_until(time(7, 30)),
+            Duration::from_millis(90 * 60 * 1000)
+        );
+        assert_eq!(time(7, 0).duration_until(time(7, 0)), Duration::ZERO);
+    }
+
+    #[test]
+    fn duration_until_across_midnight() {
+        assert_eq!(
+            time(23, 0).duration_until(time(1, 0)),
+            Duration::from_millis(2 * 60 * 60 * 1000)
+        );
+        assert_eq!(
+            TimeOfDay::new(23, 59, 59)
+                .unwrap()
+                .duration_until(TimeOfDay::MIDNIGHT),
+            Duration::ONE_SECOND
+        );
+        assert_eq!(
+            time(1, 0).duration_until(time(0, 59)),
+            Duration::from_millis((SECONDS_PER_DAY - 60) * 1000)
+        );
+    }
+
+    #[test]
+    fn mock_clock_wraps_around_midnight() {
+        let mut rtc = MockRealTimeClock::new();
+        assert_eq!(rtc.time_of_day(), None);
+        rtc.advance(Duration::ONE_HOUR);
+        assert_eq!(rtc.time_of_day(), None);
+
+        rtc.set_time_of_day(time(23, 30)).unwrap();
+        rtc.advance(Duration::ONE_HOUR);
+        assert_eq!(rtc.time_of_day(), Some(time(0, 30)));
+    }
+}
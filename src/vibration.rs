@@ -0,0 +1,123 @@
+use crate::{Debouncer, Duration, Edge, Instant};
+use core::convert::Infallible;
+use embedded_hal::digital::v2::InputPin;
+use log::debug;
+
+/// How long the switch must be closed before it counts as a vibration
+pub const VIBRATION_DEBOUNCE_DURATION: Duration = Duration::from_millis(10);
+
+/// Minimum time between two triggers
+pub const VIBRATION_COOLDOWN_DURATION: Duration = Duration::from_millis(10 * 1000);
+
+/// Vibration sensor switch, pulled up, the switch closes to ground when shaken
+pub struct VibrationSensor<P> {
+    pin: P,
+    debouncer: Debouncer,
+    triggered_at: Option<Instant>,
+}
+
+impl<P> VibrationSensor<P>
+where
+    P: InputPin<Error = Infallible>,
+{
+    pub fn new(pin: P, now: Instant) -> Self {
+        VibrationSensor {
+            pin,
+            debouncer: Debouncer::new(VIBRATION_DEBOUNCE_DURATION, false, now),
+            triggered_at: None,
+        }
+    }
+
+    /// Sample the pin, call this periodically, 1~5 ms should do.
+    /// Returns true when a vibration was detected and the cooldown has elapsed.
+    pub fn poll(&mut self, now: Instant) -> bool {
+        // Unwrap ok, infallible
+        let active = self.pin.is_low().unwrap();
+
+        // Cleared as soon as the cooldown elapses so it never spans the clock wrap around
+        if let Some(t) = self.triggered_at {
+            if now.duration_since(t) >= VIBRATION_COOLDOWN_DURATION {
+                self.triggered_at = None;
+            }
+        }
+
+        match self.debouncer.update(active, now) {
+            Some(Edge::Rising) if self.triggered_at.is_none() => {
+                debug!("Vibration detected at {}", now);
+                self.triggered_at = Some(now);
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::cell::Cell;
+
+    /// Pin level set by the test, true is high
+    struct ScriptedPin<'a>(&'a Cell<bool>);
+
+    impl<'a> InputPin for ScriptedPin<'a> {
+        type Error = Infallible;
+
+        fn is_high(&self) -> Result<bool, Infallible> {
+            Ok(self.0.get())
+        }
+
+        fn is_low(&self) -> Result<bool, Infallible> {
+            Ok(!self.0.get())
+        }
+    }
+
+    /// Shakes the sensor at `at` for 50 ms, polled every millisecond,
+    /// returns the number of triggers
+    fn shake(sensor: &mut VibrationSensor<ScriptedPin>, pin: &Cell<bool>, at: u32) -> usize {
+        let mut triggers = 0;
+        for t in at..at + 100 {
+            pin.set(t >= at + 50);
+            if sensor.poll(Instant::from_millis(t)) {
+                triggers += 1;
+            }
+        }
+        triggers
+    }
+
+    #[test]
+    fn retrigger_within_cooldown_is_ignored() {
+        let pin = Cell::new(true);
+        let mut sensor = VibrationSensor::new(ScriptedPin(&pin), Instant::ZERO);
+
+        assert_eq!(shake(&mut sensor, &pin, 0), 1);
+        assert_eq!(shake(&mut sensor, &pin, 1000), 0);
+        let cooldown = VIBRATION_COOLDOWN_DURATION.as_millis();
+        assert_eq!(shake(&mut sensor, &pin, cooldown - 200), 0);
+    }
+
+    #[test]
+    fn retrigger_after_cooldown() {
+        let pin = Cell::new(true);
+        let mut sensor = VibrationSensor::new(ScriptedPin(&pin), Instant::ZERO);
+
+        assert_eq!(shake(&mut sensor, &pin, 0), 1);
+        let cooldown = VIBRATION_COOLDOWN_DURATION.as_millis();
+        assert_eq!(shake(&mut sensor, &pin, cooldown), 1);
+        assert_eq!(shake(&mut sensor, &pin, 3 * cooldown), 1);
+    }
+
+    #[test]
+    fn short_contact_is_ignored() {
+        let pin = Cell::new(true);
+        let mut sensor = VibrationSensor::new(ScriptedPin(&pin), Instant::ZERO);
+
+        let debounce = VIBRATION_DEBOUNCE_DURATION.as_millis();
+        let mut triggered = false;
+        for t in 0..100 {
+            pin.set(!(10..10 + debounce - 1).contains(&t));
+            triggered |= sensor.poll(Instant::from_millis(t));
+        }
+        assert!(!triggered);
+    }
+}